@@ -0,0 +1,377 @@
+//! Persistent user accounts, documented in the README.
+//!
+//! Accounts and login sessions live in the embedded database, passwords are hashed with
+//! argon2. Logged in users are recognized by the `session` cookie, everyone else plays as a
+//! guest.
+
+use crate::AppState;
+use argon2::{
+    password_hash::{self, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, State},
+    http::{header, request::Parts, HeaderMap, HeaderValue, StatusCode},
+    routing::{get, post},
+    Json, Router,
+};
+use common::http::{DisplayNameRequest, LoginRequest, RegisterRequest, UserInfo};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::{
+    convert::Infallible,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use uuid::Uuid;
+
+const SESSION_COOKIE: &str = "session";
+const SESSION_LIFETIME: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+const MAX_NAME_LEN: usize = 32;
+const MIN_PASSWORD_LEN: usize = 8;
+/// Keeps hashing cheap for absurdly long passwords
+const MAX_PASSWORD_LEN: usize = 256;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct User {
+    username: String,
+    display_name: String,
+    /// Argon2 hash in PHC string format, including the salt
+    password_hash: String,
+}
+
+impl User {
+    fn info(&self) -> UserInfo {
+        UserInfo {
+            username: self.username.clone(),
+            display_name: self.display_name.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Session {
+    username: String,
+    /// Seconds since the unix epoch
+    expires: u64,
+}
+
+/// Accounts by lowercase username and login sessions by token
+pub struct Accounts {
+    users: sled::Tree,
+    sessions: sled::Tree,
+    /// Verified instead of a password hash when logging in as an unknown user, so the response
+    /// time doesn't reveal which usernames exist
+    dummy_hash: String,
+}
+
+impl Accounts {
+    pub fn open(db: &sled::Db) -> sled::Result<Self> {
+        Ok(Self {
+            users: db.open_tree("users")?,
+            sessions: db.open_tree("sessions")?,
+            dummy_hash: hash(&rand::random::<[u8; 16]>())
+                .expect("the default argon2 parameters are valid"),
+        })
+    }
+
+    fn user(&self, username: &str) -> sled::Result<Option<User>> {
+        Ok(self
+            .users
+            .get(username.to_lowercase())?
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok()))
+    }
+
+    pub fn user_info(&self, username: &str) -> sled::Result<Option<UserInfo>> {
+        Ok(self.user(username)?.map(|user| user.info()))
+    }
+
+    /// Stores a new user, returns false if the username is taken.
+    fn insert_user(&self, user: &User) -> sled::Result<bool> {
+        let bytes = serde_json::to_vec(user).expect("users are serializable");
+        Ok(self
+            .users
+            .compare_and_swap(user.username.to_lowercase(), None::<&[u8]>, Some(bytes))?
+            .is_ok())
+    }
+
+    fn update_user(&self, user: &User) -> sled::Result<()> {
+        let bytes = serde_json::to_vec(user).expect("users are serializable");
+        self.users.insert(user.username.to_lowercase(), bytes)?;
+        Ok(())
+    }
+
+    fn create_session(&self, username: &str) -> sled::Result<String> {
+        let token = Uuid::new_v4().simple().to_string();
+        let session = Session {
+            username: username.to_string(),
+            expires: unix_time() + SESSION_LIFETIME.as_secs(),
+        };
+        let bytes = serde_json::to_vec(&session).expect("sessions are serializable");
+        self.sessions.insert(token.as_bytes(), bytes)?;
+        Ok(token)
+    }
+
+    fn session_user(&self, token: &str) -> sled::Result<Option<User>> {
+        let Some(session) = self
+            .sessions
+            .get(token.as_bytes())?
+            .and_then(|bytes| serde_json::from_slice::<Session>(&bytes).ok())
+        else {
+            return Ok(None);
+        };
+        if session.expires <= unix_time() {
+            self.sessions.remove(token.as_bytes())?;
+            return Ok(None);
+        }
+        self.user(&session.username)
+    }
+
+    fn remove_session(&self, token: &str) -> sled::Result<()> {
+        self.sessions.remove(token.as_bytes())?;
+        Ok(())
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn internal_error(err: impl std::fmt::Display) -> StatusCode {
+    error!("account storage failed: {err}");
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/register", post(register))
+        .route("/login", post(login))
+        .route("/logout", post(logout))
+        .route("/me", get(me))
+        .route("/display_name", post(set_display_name))
+}
+
+fn session_token(parts: &Parts) -> Option<&str> {
+    parts
+        .headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        .map(|(_, token)| token)
+}
+
+/// The logged in user, `None` for guests
+pub struct CurrentUser(pub Option<UserInfo>);
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for CurrentUser {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let Some(token) = session_token(parts) else {
+            return Ok(Self(None));
+        };
+        match state.accounts.session_user(token) {
+            Ok(user) => Ok(Self(user.map(|user| user.info()))),
+            Err(err) => {
+                // playing as a guest still works without the database
+                error!("failed to look up session: {err}");
+                Ok(Self(None))
+            }
+        }
+    }
+}
+
+/// Token of the login session, taken from the `session` cookie
+struct SessionToken(String);
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for SessionToken {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        session_token(parts)
+            .map(|token| Self(token.to_string()))
+            .ok_or(StatusCode::UNAUTHORIZED)
+    }
+}
+
+/// Whether the request reached a reverse proxy over HTTPS, the backend itself only speaks HTTP
+fn is_https(headers: &HeaderMap) -> bool {
+    headers
+        .get("x-forwarded-proto")
+        .and_then(|proto| proto.to_str().ok())
+        .is_some_and(|proto| proto.trim().eq_ignore_ascii_case("https"))
+}
+
+/// The `session` cookie, which is only sent over HTTPS if the request came in over HTTPS
+fn session_cookie(token: &str, max_age: Duration, request: &HeaderMap) -> HeaderMap {
+    let mut cookie = format!(
+        "{SESSION_COOKIE}={token}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax",
+        max_age.as_secs()
+    );
+    if is_https(request) {
+        cookie.push_str("; Secure");
+    }
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::SET_COOKIE,
+        HeaderValue::from_str(&cookie).expect("tokens are valid header values"),
+    );
+    headers
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().count() <= MAX_NAME_LEN
+}
+
+fn valid_username(username: &str) -> bool {
+    valid_name(username)
+        && username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Hashes `password` with a random salt.
+fn hash(password: &[u8]) -> password_hash::Result<String> {
+    let salt =
+        SaltString::encode_b64(&rand::random::<[u8; 16]>()).expect("16 bytes are a valid salt");
+    Argon2::default()
+        .hash_password(password, &salt)
+        .map(|hash| hash.to_string())
+}
+
+/// Runs the slow password hashing off the async runtime.
+async fn hash_password(password: String) -> Result<String, StatusCode> {
+    tokio::task::spawn_blocking(move || hash(password.as_bytes()))
+        .await
+        .map_err(internal_error)?
+        .map_err(internal_error)
+}
+
+async fn verify_password(password: String, hash: String) -> bool {
+    tokio::task::spawn_blocking(move || {
+        PasswordHash::new(&hash).is_ok_and(|hash| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+        })
+    })
+    .await
+    .unwrap_or(false)
+}
+
+async fn register(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(request): Json<RegisterRequest>,
+) -> Result<(HeaderMap, Json<UserInfo>), StatusCode> {
+    let username = request.username.trim();
+    let display_name = request
+        .display_name
+        .as_deref()
+        .map(str::trim)
+        .unwrap_or(username);
+    if !valid_username(username)
+        || !valid_name(display_name)
+        || !(MIN_PASSWORD_LEN..=MAX_PASSWORD_LEN).contains(&request.password.len())
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let user = User {
+        username: username.to_string(),
+        display_name: display_name.to_string(),
+        password_hash: hash_password(request.password).await?,
+    };
+    if !state.accounts.insert_user(&user).map_err(internal_error)? {
+        return Err(StatusCode::CONFLICT);
+    }
+    info!("registered user '{}'", user.username);
+    let token = state
+        .accounts
+        .create_session(&user.username)
+        .map_err(internal_error)?;
+    Ok((
+        session_cookie(&token, SESSION_LIFETIME, &headers),
+        Json(user.info()),
+    ))
+}
+
+async fn login(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(request): Json<LoginRequest>,
+) -> Result<(HeaderMap, Json<UserInfo>), StatusCode> {
+    if request.password.len() > MAX_PASSWORD_LEN {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    let user = state
+        .accounts
+        .user(request.username.trim())
+        .map_err(internal_error)?;
+    let hash = user.as_ref().map_or_else(
+        || state.accounts.dummy_hash.clone(),
+        |user| user.password_hash.clone(),
+    );
+    // unknown usernames take as long to reject as wrong passwords
+    let verified = verify_password(request.password, hash).await;
+    let user = user.filter(|_| verified).ok_or(StatusCode::UNAUTHORIZED)?;
+    let token = state
+        .accounts
+        .create_session(&user.username)
+        .map_err(internal_error)?;
+    Ok((
+        session_cookie(&token, SESSION_LIFETIME, &headers),
+        Json(user.info()),
+    ))
+}
+
+async fn logout(
+    token: SessionToken,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<HeaderMap, StatusCode> {
+    state
+        .accounts
+        .remove_session(&token.0)
+        .map_err(internal_error)?;
+    Ok(session_cookie("", Duration::ZERO, &headers))
+}
+
+async fn me(CurrentUser(user): CurrentUser) -> Result<Json<UserInfo>, StatusCode> {
+    user.map(Json).ok_or(StatusCode::UNAUTHORIZED)
+}
+
+async fn set_display_name(
+    CurrentUser(user): CurrentUser,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<DisplayNameRequest>,
+) -> Result<Json<UserInfo>, StatusCode> {
+    let user = user.ok_or(StatusCode::UNAUTHORIZED)?;
+    let display_name = request.display_name.trim();
+    if !valid_name(display_name) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let mut user = state
+        .accounts
+        .user(&user.username)
+        .map_err(internal_error)?
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    user.display_name = display_name.to_string();
+    state.accounts.update_user(&user).map_err(internal_error)?;
+    Ok(Json(user.info()))
+}
@@ -0,0 +1,70 @@
+//! Opponents played by the server itself.
+
+use crate::uci;
+use common::notation;
+use cozy_chess::{Board, Move};
+use engine::{Engine, Limits};
+use std::{
+    io::{self, ErrorKind},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+pub enum Bot {
+    /// External engine process speaking UCI
+    Uci {
+        engine: Box<uci::Engine>,
+        move_time: Duration,
+    },
+    /// The built-in engine, searching on the blocking thread pool
+    Builtin {
+        engine: Arc<Mutex<Engine>>,
+        limits: Limits,
+    },
+}
+
+impl Bot {
+    pub async fn uci(path: &Path, move_time: Duration) -> io::Result<Self> {
+        Ok(Self::Uci {
+            engine: Box::new(uci::Engine::spawn(path).await?),
+            move_time,
+        })
+    }
+
+    pub fn builtin(level: u8) -> Self {
+        Self::Builtin {
+            engine: Arc::new(Mutex::new(Engine::with_seed(rand::random()))),
+            limits: Limits::level(level),
+        }
+    }
+
+    /// Picks a move for `board`, which was reached by playing the UCI `moves` from `fen`.
+    pub async fn best_move(
+        &mut self,
+        board: &Board,
+        fen: &str,
+        moves: &[String],
+    ) -> io::Result<Move> {
+        match self {
+            Self::Uci { engine, move_time } => {
+                let best_move = engine.best_move(fen, moves, *move_time).await?;
+                notation::parse_uci(board, &best_move)
+                    .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))
+            }
+            Self::Builtin { engine, limits } => {
+                let engine = Arc::clone(engine);
+                let board = board.clone();
+                let limits = *limits;
+                let result = tokio::task::spawn_blocking(move || {
+                    engine.lock().unwrap().search(&board, limits)
+                })
+                .await
+                .map_err(io::Error::other)?;
+                result.map(|result| result.best_move).ok_or_else(|| {
+                    io::Error::new(ErrorKind::InvalidData, "no legal moves in position")
+                })
+            }
+        }
+    }
+}
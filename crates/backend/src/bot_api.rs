@@ -0,0 +1,381 @@
+//! HTTP API for external programs playing as a seat, documented in the README.
+//!
+//! Challenges are ordinary lobbies, so humans can accept them through `/api/join/:id` and bots
+//! can accept lobbies hosted by humans. Bots belong to a user account and are stored in the
+//! database, the games they play only live as long as the lobbies.
+
+use crate::{
+    accounts::CurrentUser, create_lobby, join_lobby, valid_settings, AppState, Lobby, LobbyState,
+    MoveOutcome, Player, PlayerAction,
+};
+use axum::{
+    async_trait,
+    body::StreamBody,
+    extract::{FromRequestParts, Path, State},
+    http::{header, request::Parts, StatusCode},
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use common::{
+    http::{BotAccountRequest, BotAccountResponse, ChallengeResponse, LobbySettings},
+    notation,
+    ws::{encoding::Encoding, GameState, ServerMsg},
+};
+use cozy_chess::Board;
+use futures::{
+    channel::mpsc::{self, Sender},
+    lock::Mutex,
+    SinkExt,
+};
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, convert::Infallible, sync::Arc};
+use tokio::sync::{broadcast::error::RecvError, oneshot};
+use uuid::Uuid;
+
+const MAX_NAME_LEN: usize = 32;
+/// Bots a single user can register
+const MAX_BOTS_PER_USER: usize = 10;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BotAccount {
+    name: String,
+    /// Username of the user who registered the bot
+    owner: String,
+}
+
+/// Registered bots and the games they play
+pub struct BotAccounts {
+    /// Bots by token
+    bots: sled::Tree,
+    /// Keys are `<lowercase owner>/<token>`, to count the bots of a user
+    owners: sled::Tree,
+    /// Session of every game a bot takes part in by lobby id, by token
+    games: Mutex<HashMap<String, HashMap<Uuid, Uuid>>>,
+}
+
+impl BotAccounts {
+    pub fn open(db: &sled::Db) -> sled::Result<Self> {
+        Ok(Self {
+            bots: db.open_tree("bots")?,
+            owners: db.open_tree("bot_owners")?,
+            games: Mutex::default(),
+        })
+    }
+
+    fn bot(&self, token: &str) -> sled::Result<Option<BotAccount>> {
+        Ok(self
+            .bots
+            .get(token.as_bytes())?
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok()))
+    }
+
+    fn owner_key(owner: &str, token: &str) -> String {
+        format!("{}/{token}", owner.to_lowercase())
+    }
+
+    fn count(&self, owner: &str) -> usize {
+        self.owners.scan_prefix(Self::owner_key(owner, "")).count()
+    }
+
+    fn insert(&self, token: &str, bot: &BotAccount) -> sled::Result<()> {
+        let bytes = serde_json::to_vec(bot).expect("bots are serializable");
+        self.bots.insert(token.as_bytes(), bytes)?;
+        self.owners
+            .insert(Self::owner_key(&bot.owner, token), &[])?;
+        Ok(())
+    }
+}
+
+fn internal_error(err: impl std::fmt::Display) -> StatusCode {
+    error!("bot storage failed: {err}");
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/account", post(create_account))
+        .route("/challenge", post(create_challenge))
+        .route("/challenge/:id/accept", post(accept_challenge))
+        .route("/game/:id/stream", get(stream_game))
+        .route("/game/:id/move/:uci", post(play_move))
+}
+
+/// Token of a registered bot, taken from the `Authorization: Bearer <token>` header
+struct BotToken(String);
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for BotToken {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let token = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+        match state.bots.bot(token).map_err(internal_error)? {
+            Some(_) => Ok(Self(token.to_string())),
+            None => Err(StatusCode::UNAUTHORIZED),
+        }
+    }
+}
+
+/// Registers a bot for the logged in user.
+async fn create_account(
+    CurrentUser(user): CurrentUser,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<BotAccountRequest>,
+) -> Result<Json<BotAccountResponse>, StatusCode> {
+    let user = user.ok_or(StatusCode::UNAUTHORIZED)?;
+    let name = request.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if state.bots.count(&user.username) >= MAX_BOTS_PER_USER {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let token = Uuid::new_v4().simple().to_string();
+    let bot = BotAccount {
+        name: name.to_string(),
+        owner: user.username,
+    };
+    state.bots.insert(&token, &bot).map_err(internal_error)?;
+    info!("user '{}' registered bot '{name}'", bot.owner);
+    Ok(Json(BotAccountResponse { token }))
+}
+
+/// Bots play under their registered name
+fn bot_player(state: &AppState, token: &BotToken) -> Result<Player, StatusCode> {
+    let bot = state
+        .bots
+        .bot(&token.0)
+        .map_err(internal_error)?
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    Ok(Player {
+        display_name: bot.name,
+        username: None,
+    })
+}
+
+/// Remembers the bot's session in a lobby and forgets the games that are over.
+async fn add_game(state: &AppState, token: &BotToken, lobby_id: Uuid, session: Uuid) {
+    let lobbies = state.lobbies.lock().await;
+    let mut games = state.bots.games.lock().await;
+    let bot_games = games.entry(token.0.clone()).or_default();
+    bot_games.retain(|id, _| {
+        lobbies
+            .get(id)
+            .is_some_and(|lobby| lobby.game_over.is_none())
+    });
+    debug!("bot takes a seat in lobby {lobby_id}");
+    bot_games.insert(lobby_id, session);
+}
+
+/// Takes optional [`LobbySettings`], e.g. to list the challenge publicly.
+async fn create_challenge(
+    token: BotToken,
+    State(state): State<Arc<AppState>>,
+    settings: Option<Json<LobbySettings>>,
+) -> Result<Json<ChallengeResponse>, StatusCode> {
+    let settings = settings.map(|Json(settings)| settings).unwrap_or_default();
+    // bots have no rating
+    if !valid_settings(&settings) || settings.rated {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let game = create_lobby(
+        &state,
+        bot_player(&state, &token)?,
+        settings,
+        Board::default(),
+    )
+    .await;
+    add_game(&state, &token, game.lobby_id, game.session).await;
+    Ok(Json(ChallengeResponse {
+        game_id: game.lobby_id,
+    }))
+}
+
+async fn accept_challenge(
+    token: BotToken,
+    Path(id): Path<Uuid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ChallengeResponse>, StatusCode> {
+    let game = join_lobby(&state, id, bot_player(&state, &token)?).await?;
+    add_game(&state, &token, game.lobby_id, game.session).await;
+    Ok(Json(ChallengeResponse { game_id: id }))
+}
+
+async fn find_session(state: &AppState, token: &BotToken, id: Uuid) -> Result<Uuid, StatusCode> {
+    state
+        .bots
+        .games
+        .lock()
+        .await
+        .get(&token.0)
+        .and_then(|games| games.get(&id).copied())
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn play_move(
+    token: BotToken,
+    Path((id, uci)): Path<(Uuid, String)>,
+    State(state): State<Arc<AppState>>,
+) -> Result<StatusCode, StatusCode> {
+    let session = find_session(&state, &token, id).await?;
+    let board = match state
+        .lobbies
+        .lock()
+        .await
+        .get(&id)
+        .map(|lobby| &lobby.state)
+    {
+        Some(LobbyState::Playing {
+            board, sessions, ..
+        }) => {
+            if Some(board.side_to_move()) != sessions.find(session) {
+                return Err(StatusCode::CONFLICT);
+            }
+            board.clone()
+        }
+        Some(LobbyState::Waiting { .. }) => return Err(StatusCode::CONFLICT),
+        None => return Err(StatusCode::NOT_FOUND),
+    };
+    let chess_move = notation::parse_uci(&board, &uci).map_err(|err| {
+        debug!("bot sent bad move: {err}");
+        StatusCode::BAD_REQUEST
+    })?;
+
+    let (outcome_tx, outcome_rx) = oneshot::channel();
+    state
+        .tx
+        .clone()
+        .send(PlayerAction::PlayMove {
+            lobby_id: id,
+            session,
+            chess_move,
+            outcome: Some(outcome_tx),
+        })
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    match outcome_rx.await {
+        Ok(MoveOutcome::Played) => Ok(StatusCode::OK),
+        Ok(MoveOutcome::NotYourTurn | MoveOutcome::NotStarted | MoveOutcome::GameOver) => {
+            Err(StatusCode::CONFLICT)
+        }
+        Ok(MoveOutcome::Illegal) => Err(StatusCode::BAD_REQUEST),
+        Ok(MoveOutcome::UnknownLobby) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+/// Sends `msg` as a line of JSON, returns false once the client is gone.
+async fn send_event(events: &mut Sender<Result<Vec<u8>, Infallible>>, msg: &ServerMsg) -> bool {
+    let Ok(mut line) = Encoding::Json.encode(msg) else {
+        return false;
+    };
+    line.push(b'\n');
+    events.send(Ok(line)).await.is_ok()
+}
+
+/// Streams the game as newline delimited JSON messages of the websocket protocol.
+///
+/// The stream starts with `connected` and ends with `game_over`. When the opponent joins,
+/// `opponent_joined` is followed by another `connected` with the bot's color.
+async fn stream_game(
+    token: BotToken,
+    Path(id): Path<Uuid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let session = find_session(&state, &token, id).await?;
+    let (mut rx, first, players, clocks, game_over) = {
+        let lobbies = state.lobbies.lock().await;
+        let lobby = lobbies.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+        let first = lobby.game_state(session).ok_or(StatusCode::NOT_FOUND)?;
+        (
+            lobby.tx.subscribe(),
+            first,
+            lobby.players(),
+            lobby.clocks(),
+            lobby.game_over.clone(),
+        )
+    };
+
+    let (mut events, body) = mpsc::channel(8);
+    let state = Arc::clone(&state);
+    tokio::spawn(async move {
+        if !send_event(&mut events, &ServerMsg::Connected(first)).await {
+            return;
+        }
+        for msg in players.into_iter().chain(clocks) {
+            if !send_event(&mut events, &msg).await {
+                return;
+            }
+        }
+        if let Some(game_over) = game_over {
+            send_event(&mut events, &game_over).await;
+            return;
+        }
+        loop {
+            let msgs = match rx.recv().await {
+                Ok(msg) => vec![msg],
+                Err(RecvError::Lagged(skipped)) => {
+                    debug!("bot stream of lobby {id} missed {skipped} messages, resyncing");
+                    match state.lobbies.lock().await.get(&id) {
+                        Some(lobby) => lobby.resync(session),
+                        None => return,
+                    }
+                }
+                Err(RecvError::Closed) => return,
+            };
+            for msg in msgs {
+                if !send_event(&mut events, &msg).await {
+                    return;
+                }
+                match msg {
+                    ServerMsg::OpponentJoined => {
+                        // moves played since the game started from the lobby's start position
+                        // are still queued in `rx`
+                        let (color, players, clocks, fen) =
+                            match state.lobbies.lock().await.get(&id) {
+                                Some(
+                                    lobby @ Lobby {
+                                        state: LobbyState::Playing { sessions, .. },
+                                        start,
+                                        ..
+                                    },
+                                ) => (
+                                    sessions.find(session),
+                                    lobby.players(),
+                                    lobby.clocks(),
+                                    start.to_string(),
+                                ),
+                                _ => return,
+                            };
+                        let (Some(color), Some(players)) = (color, players) else {
+                            return;
+                        };
+                        let connected = ServerMsg::Connected(GameState::Ingame { fen, color });
+                        for msg in [connected, players].into_iter().chain(clocks) {
+                            if !send_event(&mut events, &msg).await {
+                                return;
+                            }
+                        }
+                    }
+                    ServerMsg::GameOver { .. } => return,
+                    _ => {}
+                }
+            }
+        }
+    });
+
+    Ok((
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        StreamBody::new(body),
+    ))
+}
@@ -0,0 +1,1712 @@
+//! Game server: lobbies, matchmaking, accounts and the WebSocket protocol, served by [`app`].
+
+use accounts::{Accounts, CurrentUser};
+use axum::{
+    extract::{ws::WebSocket, Path, Query, State, WebSocketUpgrade},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    routing::{self, get_service},
+    Json, Router,
+};
+use bot::Bot;
+use bot_api::BotAccounts;
+use common::{
+    http::{
+        BotSettings, GameRecord, HostRequest, HostResponse, JoinResponse, LeaderboardEntry,
+        LobbyInfo, LobbySettings, RatingCategory, SeekRequest, SeekResponse, TimeControl, UserInfo,
+    },
+    notation, pgn,
+    ws::{
+        encoding::Encoding,
+        message::{Limits, Version},
+        ClientMsg, GameOverReason, GameResult, GameState, RatingChange, ServerMsg,
+    },
+};
+use cozy_chess::{Board, Color, GameStatus, Move, Piece};
+use futures::{
+    channel::mpsc::{channel, Sender},
+    lock::Mutex,
+    SinkExt, StreamExt,
+};
+use log::{debug, error, info, warn};
+use matchmaking::{Matchmaking, Pairing};
+use ratings::Ratings;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    env,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::{
+    broadcast::{self, error::RecvError},
+    oneshot,
+};
+use tower_http::services::{ServeDir, ServeFile};
+
+use uuid::Uuid;
+
+mod accounts;
+mod bot;
+mod bot_api;
+mod matchmaking;
+mod ratings;
+mod uci;
+
+/// Upper bound for the thinking time a host can request for the engine
+const MAX_ENGINE_MOVE_TIME: Duration = Duration::from_secs(10);
+
+/// Public lobbies nobody joined are closed after this long
+const PUBLIC_LOBBY_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Finished games are kept this long for rematches and PGN downloads
+const FINISHED_LOBBY_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// Lobbies where nobody joined or moved for this long are closed
+const IDLE_LOBBY_TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Number of players returned by `/api/leaderboard/:category`
+const LEADERBOARD_SIZE: usize = 50;
+
+/// How often running clocks are checked for players who ran out of time
+const CLOCK_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+enum PlayerAction {
+    PlayMove {
+        lobby_id: Uuid,
+        session: Uuid,
+        chess_move: Move,
+        /// Receives whether the move was played, for callers that need to report it
+        outcome: Option<oneshot::Sender<MoveOutcome>>,
+    },
+    RequestTakeback {
+        lobby_id: Uuid,
+        session: Uuid,
+    },
+    AnswerTakeback {
+        lobby_id: Uuid,
+        session: Uuid,
+        accept: bool,
+    },
+    OfferRematch {
+        lobby_id: Uuid,
+        session: Uuid,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MoveOutcome {
+    Played,
+    NotYourTurn,
+    Illegal,
+    NotStarted,
+    GameOver,
+    UnknownLobby,
+}
+
+struct AppState {
+    lobbies: Arc<Mutex<HashMap<Uuid, Lobby>>>,
+    tx: Sender<PlayerAction>,
+    bots: BotAccounts,
+    accounts: Accounts,
+    ratings: Ratings,
+    matchmaking: Mutex<Matchmaking>,
+    /// [`ServerMsg::LobbyOpened`] and [`ServerMsg::LobbyClosed`] for clients watching the lobby list
+    lobby_feed: broadcast::Sender<ServerMsg>,
+    config: Config,
+}
+
+/// Server configuration, read from environment variables
+pub struct Config {
+    /// Path of the UCI engine used for bot games (`ENGINE_PATH`), the built-in engine is used if not set
+    pub engine: Option<PathBuf>,
+    /// Default thinking time of the engine per move (`ENGINE_MOVE_TIME`, in milliseconds)
+    pub engine_move_time: Duration,
+    /// Directory of the database storing accounts (`DATABASE_PATH`), defaults to `./data`
+    pub database: PathBuf,
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        let engine = env::var_os("ENGINE_PATH").map(PathBuf::from);
+        let engine_move_time = match env::var("ENGINE_MOVE_TIME") {
+            Ok(millis) => match millis.parse() {
+                Ok(millis) => Duration::from_millis(millis),
+                Err(err) => {
+                    warn!("invalid ENGINE_MOVE_TIME '{millis}' ({err}), using default");
+                    Duration::from_secs(1)
+                }
+            },
+            Err(_) => Duration::from_secs(1),
+        };
+        match &engine {
+            Some(path) => info!("bot games use engine {}", path.display()),
+            None => info!("ENGINE_PATH not set, bot games use the built-in engine"),
+        }
+        let database = env::var_os("DATABASE_PATH")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("./data"));
+        Self {
+            engine,
+            engine_move_time,
+            database,
+        }
+    }
+}
+
+/// Query parameters of the `/ws` endpoint
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct ConnectParams {
+    /// Binary protocol version the client speaks, defaults to [`Version::CURRENT`]
+    #[serde(default)]
+    version: Version,
+    /// Encoding of the connection, detected from the first frame if not specified
+    encoding: Option<EncodingParam>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum EncodingParam {
+    Binary,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Sessions {
+    white: Uuid,
+    black: Uuid,
+}
+
+impl Sessions {
+    fn find(&self, session: Uuid) -> Option<Color> {
+        if self.white == session {
+            Some(Color::White)
+        } else if self.black == session {
+            Some(Color::Black)
+        } else {
+            None
+        }
+    }
+}
+
+/// Who sits on a seat, shown to the opponent
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Player {
+    display_name: String,
+    /// Account of the player, `None` for guests and engines
+    username: Option<String>,
+}
+
+impl Player {
+    fn guest() -> Self {
+        Self {
+            display_name: "Guest".to_string(),
+            username: None,
+        }
+    }
+
+    fn computer() -> Self {
+        Self {
+            display_name: "Computer".to_string(),
+            username: None,
+        }
+    }
+}
+
+impl From<Option<UserInfo>> for Player {
+    fn from(user: Option<UserInfo>) -> Self {
+        match user {
+            Some(user) => Self {
+                display_name: user.display_name,
+                username: Some(user.username),
+            },
+            None => Self::guest(),
+        }
+    }
+}
+
+/// Remaining time of both players in a game with a time control
+#[derive(Debug, Clone)]
+struct Clock {
+    remaining: [Duration; Color::NUM],
+    increment: Duration,
+    /// Side whose time is running and since when, the clock starts with the first move
+    running: Option<(Color, Instant)>,
+}
+
+impl Clock {
+    fn new(time_control: TimeControl) -> Self {
+        Self {
+            remaining: [Duration::from_secs(time_control.initial.into()); Color::NUM],
+            increment: Duration::from_secs(time_control.increment.into()),
+            running: None,
+        }
+    }
+
+    /// Time left for `color`, including their running turn
+    fn remaining(&self, color: Color) -> Duration {
+        match self.running {
+            Some((running, since)) if running == color => {
+                self.remaining[color as usize].saturating_sub(since.elapsed())
+            }
+            _ => self.remaining[color as usize],
+        }
+    }
+
+    /// Side whose time ran out
+    fn flagged(&self) -> Option<Color> {
+        let (color, _) = self.running?;
+        self.remaining(color).is_zero().then_some(color)
+    }
+
+    /// Charges the running side for their turn and runs the time of `color` instead
+    fn run(&mut self, color: Option<Color>) {
+        if let Some((running, _)) = self.running {
+            self.remaining[running as usize] = self.remaining(running);
+        }
+        self.running = color.map(|color| (color, Instant::now()));
+    }
+
+    /// `color` played a move, they get the increment and the opponent's time starts running
+    fn moved(&mut self, color: Color) {
+        self.run(None);
+        self.remaining[color as usize] += self.increment;
+        self.run(Some(!color));
+    }
+
+    fn msg(&self) -> ServerMsg {
+        let millis = |color| self.remaining(color).as_millis() as u64;
+        ServerMsg::Clocks {
+            white: millis(Color::White),
+            black: millis(Color::Black),
+            running: self.running.is_some(),
+        }
+    }
+}
+
+/// Messages a lobby's broadcast channel buffers, receivers falling further behind are resynced
+/// with [`Lobby::resync`]
+const LOBBY_CHANNEL_CAPACITY: usize = 64;
+
+struct Lobby {
+    tx: broadcast::Sender<ServerMsg>,
+    state: LobbyState,
+    settings: LobbySettings,
+    /// Position the games of the lobby start from
+    start: Board,
+    /// Players by session
+    players: HashMap<Uuid, Player>,
+    /// Player waiting for an answer to their takeback request
+    takeback: Option<Color>,
+    /// Player who offered a rematch after the game ended
+    rematch: Option<Color>,
+    /// [`ServerMsg::GameOver`] once the game ended
+    game_over: Option<ServerMsg>,
+    /// `None` in lobbies without a time control
+    clock: Option<Clock>,
+    created: Instant,
+    /// Last time someone joined, moved or the game ended
+    last_activity: Instant,
+}
+
+enum RematchOffer {
+    /// The opponent didn't offer a rematch yet
+    Offered(Color),
+    /// Both players want a rematch, which is played in this new lobby
+    Accepted(Box<Lobby>),
+}
+
+impl Lobby {
+    fn new(state: LobbyState, settings: LobbySettings) -> Self {
+        Self {
+            tx: broadcast::channel(LOBBY_CHANNEL_CAPACITY).0,
+            state,
+            settings,
+            start: Board::default(),
+            players: HashMap::new(),
+            takeback: None,
+            rematch: None,
+            game_over: None,
+            clock: settings.time_control.map(Clock::new),
+            created: Instant::now(),
+            last_activity: Instant::now(),
+        }
+    }
+
+    /// State of the lobby as seen by `session`, `None` if the session doesn't play in it
+    fn game_state(&self, session: Uuid) -> Option<GameState> {
+        match &self.state {
+            LobbyState::Waiting { session: host } => {
+                (*host == session).then_some(GameState::WaitingForOpponent)
+            }
+            LobbyState::Playing {
+                board, sessions, ..
+            } => Some(GameState::Ingame {
+                fen: board.to_string(),
+                color: sessions.find(session)?,
+            }),
+        }
+    }
+
+    /// Messages bringing a receiver that missed broadcasts up to date
+    fn resync(&self, session: Uuid) -> Vec<ServerMsg> {
+        let mut msgs: Vec<ServerMsg> = self
+            .game_state(session)
+            .map(ServerMsg::Connected)
+            .into_iter()
+            .collect();
+        msgs.extend(self.players());
+        msgs.extend(self.clocks());
+        msgs.extend(self.takeback.map(ServerMsg::TakebackRequested));
+        msgs.extend(self.rematch.map(ServerMsg::RematchOffered));
+        msgs.extend(self.game_over.clone());
+        msgs
+    }
+
+    /// [`ServerMsg::Players`] once both seats are taken
+    fn players(&self) -> Option<ServerMsg> {
+        let LobbyState::Playing { sessions, .. } = &self.state else {
+            return None;
+        };
+        let name = |session| self.display_name(session);
+        Some(ServerMsg::Players {
+            white: name(sessions.white),
+            black: name(sessions.black),
+        })
+    }
+
+    /// [`ServerMsg::Clocks`] in games with a time control
+    fn clocks(&self) -> Option<ServerMsg> {
+        match self.state {
+            LobbyState::Playing { .. } => self.clock.as_ref().map(Clock::msg),
+            LobbyState::Waiting { .. } => None,
+        }
+    }
+
+    /// Whether the side to move ran out of time in a game that isn't over yet
+    fn out_of_time(&self) -> bool {
+        self.game_over.is_none()
+            && self
+                .clock
+                .as_ref()
+                .is_some_and(|clock| clock.flagged().is_some())
+    }
+
+    /// Result of the game once it's over
+    fn result(&self) -> Option<GameResult> {
+        match self.game_over {
+            Some(ServerMsg::GameOver { result, .. }) => Some(result),
+            _ => None,
+        }
+    }
+
+    /// Why the lobby should be closed, `None` while it's still in use
+    fn expiry(&self) -> Option<&'static str> {
+        let idle = self.last_activity.elapsed();
+        if self.is_listed() && self.created.elapsed() >= PUBLIC_LOBBY_TIMEOUT {
+            Some("public lobby expired")
+        } else if self.game_over.is_some() && idle >= FINISHED_LOBBY_TIMEOUT {
+            Some("finished game expired")
+        } else if idle >= IDLE_LOBBY_TIMEOUT {
+            Some("lobby was abandoned")
+        } else {
+            None
+        }
+    }
+
+    /// Whether the lobby is listed in the public lobby list
+    fn is_listed(&self) -> bool {
+        self.settings.public && matches!(self.state, LobbyState::Waiting { .. })
+    }
+
+    fn info(&self, id: Uuid) -> LobbyInfo {
+        LobbyInfo {
+            id,
+            time_control: self.settings.time_control,
+            variant: self.settings.variant,
+            rated: self.settings.rated,
+        }
+    }
+
+    /// Returns [`ServerMsg::GameOver`] if the game just ended, updating the ratings of rated games.
+    fn finish(&mut self, ratings: &Ratings) -> Option<ServerMsg> {
+        if self.game_over.is_some() {
+            return None;
+        }
+        let LobbyState::Playing {
+            board, sessions, ..
+        } = &self.state
+        else {
+            return None;
+        };
+        let (result, reason) = match self.clock.as_ref().and_then(Clock::flagged) {
+            Some(flagged) => (timeout_result(board, flagged), GameOverReason::Timeout),
+            None => (game_result(board)?, game_over_reason(board)?),
+        };
+        let ratings = self.update_ratings(result, sessions, ratings);
+        if let Some(clock) = &mut self.clock {
+            clock.run(None);
+        }
+        self.takeback = None;
+        let msg = ServerMsg::GameOver {
+            result,
+            reason,
+            ratings,
+        };
+        self.game_over = Some(msg.clone());
+        self.last_activity = Instant::now();
+        Some(msg)
+    }
+
+    /// Ends the game if it's over and broadcasts the clocks, followed by [`ServerMsg::GameOver`]
+    /// if the game just ended
+    fn check_game_over(&mut self, ratings: &Ratings) {
+        let game_over = self.finish(ratings);
+        if let Some(clocks) = self.clocks() {
+            _ = self.tx.send(clocks);
+        }
+        if let Some(msg) = game_over {
+            _ = self.tx.send(msg);
+        }
+    }
+
+    /// Handles [`ClientMsg::OfferRematch`], the rematch keeps the sessions and swaps the colors.
+    fn offer_rematch(&mut self, session: Uuid) -> Option<RematchOffer> {
+        let LobbyState::Playing { sessions, .. } = &self.state else {
+            return None;
+        };
+        let color = sessions.find(session)?;
+        // rematches are only offered once the game is over
+        self.game_over.as_ref()?;
+        match self.rematch {
+            Some(offered) if offered == !color => {
+                self.rematch = None;
+                let sessions = Sessions {
+                    white: sessions.black,
+                    black: sessions.white,
+                };
+                let mut lobby = Lobby::new(
+                    LobbyState::playing(sessions, self.start.clone()),
+                    self.settings,
+                );
+                lobby.start = self.start.clone();
+                lobby.players = self.players.clone();
+                Some(RematchOffer::Accepted(Box::new(lobby)))
+            }
+            Some(_) => None,
+            None => {
+                self.rematch = Some(color);
+                Some(RematchOffer::Offered(color))
+            }
+        }
+    }
+
+    fn display_name(&self, session: Uuid) -> String {
+        self.players.get(&session).map_or_else(
+            || Player::guest().display_name,
+            |player| player.display_name.clone(),
+        )
+    }
+
+    /// Players and moves of the game, for `/api/game/:id`
+    fn record(&self) -> Option<GameRecord> {
+        let LobbyState::Playing {
+            sessions, moves, ..
+        } = &self.state
+        else {
+            return None;
+        };
+        let mut board = self.start.clone();
+        let moves = moves
+            .iter()
+            .map(|&chess_move| {
+                let uci = notation::to_uci(&board, chess_move);
+                board.play_unchecked(chess_move);
+                uci
+            })
+            .collect();
+        Some(GameRecord {
+            white: self.display_name(sessions.white),
+            black: self.display_name(sessions.black),
+            fen: self.start.to_string(),
+            moves,
+        })
+    }
+
+    /// The game in Portable Game Notation
+    fn pgn(&self) -> Option<String> {
+        let LobbyState::Playing {
+            sessions, moves, ..
+        } = &self.state
+        else {
+            return None;
+        };
+        let name = |session| self.display_name(session);
+        let result = self.result();
+        let time_control = self.settings.time_control.map_or_else(
+            || "-".to_string(),
+            |time_control| format!("{}+{}", time_control.initial, time_control.increment),
+        );
+        let (white, black) = (name(sessions.white), name(sessions.black));
+        let fen = self.start.to_string();
+        let mut tags = vec![
+            (
+                "Event",
+                if self.settings.rated {
+                    "Rated game"
+                } else {
+                    "Casual game"
+                },
+            ),
+            ("Site", "?"),
+            ("Date", "????.??.??"),
+            ("Round", "-"),
+            ("White", &white),
+            ("Black", &black),
+            ("Result", pgn::result(result)),
+            ("TimeControl", &time_control),
+        ];
+        if self.start != Board::default() {
+            tags.extend([("SetUp", "1"), ("FEN", &fen)]);
+        }
+        if let Some(ServerMsg::GameOver {
+            reason: GameOverReason::Timeout,
+            ..
+        }) = self.game_over
+        {
+            tags.push(("Termination", "time forfeit"));
+        }
+        Some(pgn::write(&tags, &self.start, moves, result))
+    }
+
+    /// Handles [`ClientMsg::RequestTakeback`], returns the message to broadcast.
+    fn request_takeback(&mut self, session: Uuid) -> Option<ServerMsg> {
+        let LobbyState::Playing {
+            board,
+            sessions,
+            moves,
+            ..
+        } = &self.state
+        else {
+            return None;
+        };
+        let color = sessions.find(session)?;
+        if self.takeback.is_some() {
+            return None;
+        }
+        if !self.settings.takebacks.allowed(self.settings.rated)
+            || self.game_over.is_some()
+            || takeback_plies(board, moves, color).is_none()
+        {
+            return Some(ServerMsg::TakebackDeclined);
+        }
+        self.takeback = Some(color);
+        Some(ServerMsg::TakebackRequested(color))
+    }
+
+    /// Handles [`ClientMsg::AcceptTakeback`] and [`ClientMsg::DeclineTakeback`], returns the
+    /// message to broadcast.
+    fn answer_takeback(&mut self, session: Uuid, accept: bool) -> Option<ServerMsg> {
+        let LobbyState::Playing {
+            board,
+            sessions,
+            moves,
+        } = &mut self.state
+        else {
+            return None;
+        };
+        let requester = self.takeback?;
+        if sessions.find(session)? != !requester {
+            return None;
+        }
+        self.takeback = None;
+        if !accept {
+            return Some(ServerMsg::TakebackDeclined);
+        }
+        let plies = takeback_plies(board, moves, requester)?;
+        moves.truncate(moves.len() - plies);
+        *board = self.start.clone();
+        for &chess_move in moves.iter() {
+            board.play_unchecked(chess_move);
+        }
+        if let Some(clock) = &mut self.clock {
+            // the clock stops again once every move was taken back
+            clock.run((!moves.is_empty()).then_some(board.side_to_move()));
+        }
+        Some(ServerMsg::ResetPosition {
+            fen: board.to_string(),
+            plies,
+        })
+    }
+
+    fn update_ratings(
+        &self,
+        result: GameResult,
+        sessions: &Sessions,
+        ratings: &Ratings,
+    ) -> Option<[RatingChange; 2]> {
+        if !self.settings.rated {
+            return None;
+        }
+        let category = self.settings.time_control?.category();
+        let username = |session| self.players.get(&session)?.username.as_deref();
+        let usernames = [username(sessions.white)?, username(sessions.black)?];
+        // both ratings are stored under the same key, nobody can gain rating from themselves
+        if usernames[0] == usernames[1] {
+            return None;
+        }
+        let white_score = match result {
+            GameResult::WhiteWins => 1.0,
+            GameResult::BlackWins => 0.0,
+            GameResult::Draw => 0.5,
+        };
+
+        let update = || -> sled::Result<[RatingChange; 2]> {
+            let before = [
+                ratings.get(category, usernames[0])?,
+                ratings.get(category, usernames[1])?,
+            ];
+            let after = [
+                before[0].update(&before[1], white_score),
+                before[1].update(&before[0], 1.0 - white_score),
+            ];
+            for (username, rating) in usernames.iter().zip(&after) {
+                ratings.set(category, username, rating)?;
+            }
+            Ok([0, 1].map(|i| RatingChange {
+                rating: after[i].rounded(),
+                delta: i64::from(after[i].rounded()) - i64::from(before[i].rounded()),
+            }))
+        };
+        match update() {
+            Ok(changes) => Some(changes),
+            Err(err) => {
+                error!("failed to update {category} ratings: {err}");
+                None
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum LobbyState {
+    Waiting {
+        session: Uuid,
+    },
+    Playing {
+        board: Board,
+        sessions: Sessions,
+        /// Moves played since the lobby's start position, boards can't undo moves so takebacks
+        /// replay them
+        moves: Vec<Move>,
+    },
+}
+
+impl LobbyState {
+    /// A game that starts from `start`
+    fn playing(sessions: Sessions, start: Board) -> Self {
+        Self::Playing {
+            board: start,
+            sessions,
+            moves: Vec::new(),
+        }
+    }
+}
+
+/// Result of the game, `None` while it's ongoing
+fn game_result(board: &Board) -> Option<GameResult> {
+    match board.status() {
+        GameStatus::Ongoing => None,
+        GameStatus::Won => match !board.side_to_move() {
+            Color::White => Some(GameResult::WhiteWins),
+            Color::Black => Some(GameResult::BlackWins),
+        },
+        GameStatus::Drawn => Some(GameResult::Draw),
+    }
+}
+
+/// Result of a game `flagged` lost on time, a draw if the opponent can't checkmate
+fn timeout_result(board: &Board, flagged: Color) -> GameResult {
+    if !has_mating_material(board, !flagged) {
+        return GameResult::Draw;
+    }
+    match flagged {
+        Color::White => GameResult::BlackWins,
+        Color::Black => GameResult::WhiteWins,
+    }
+}
+
+/// Whether `color` has more than a king and a single minor piece
+fn has_mating_material(board: &Board, color: Color) -> bool {
+    let pieces = board.colors(color) & !board.pieces(Piece::King);
+    let minors = pieces & (board.pieces(Piece::Knight) | board.pieces(Piece::Bishop));
+    pieces != minors || minors.len() > 1
+}
+
+/// Why the game ended, `None` while it's ongoing
+fn game_over_reason(board: &Board) -> Option<GameOverReason> {
+    match board.status() {
+        GameStatus::Ongoing => None,
+        GameStatus::Won => Some(GameOverReason::Checkmate),
+        GameStatus::Drawn if board.generate_moves(|_| true) => Some(GameOverReason::FiftyMoveRule),
+        GameStatus::Drawn => Some(GameOverReason::Stalemate),
+    }
+}
+
+/// Number of plies undone when `color` takes back their last move, along with the opponent's
+/// reply if it was already played
+fn takeback_plies(board: &Board, moves: &[Move], color: Color) -> Option<usize> {
+    let plies = if board.side_to_move() == color { 2 } else { 1 };
+    (moves.len() >= plies).then_some(plies)
+}
+
+/// Opens the database, starts the background tasks and returns the router of the server.
+///
+/// Has to be called from within a Tokio runtime.
+pub fn app(config: Config) -> sled::Result<Router> {
+    let lobbies: Arc<Mutex<HashMap<Uuid, Lobby>>> = Arc::default();
+
+    let db = sled::open(&config.database)?;
+    let accounts = Accounts::open(&db)?;
+    let ratings = Ratings::open(&db)?;
+    let bots = BotAccounts::open(&db)?;
+
+    let tx = {
+        let (tx, mut rx) = channel(100);
+        let lobbies = Arc::clone(&lobbies);
+        let ratings = ratings.clone();
+        tokio::spawn(async move {
+            while let Some(action) = rx.next().await {
+                match action {
+                    PlayerAction::PlayMove {
+                        lobby_id,
+                        session,
+                        chess_move,
+                        outcome,
+                    } => {
+                        let result = match lobbies.lock().await.get_mut(&lobby_id) {
+                            Some(lobby) => {
+                                // the sweep might not have caught a player running out of time yet
+                                if lobby.out_of_time() {
+                                    lobby.check_game_over(&ratings);
+                                }
+                                let result = match &mut lobby.state {
+                                    _ if lobby.game_over.is_some() => {
+                                        info!("client sent move after the game ended, ignoring");
+                                        MoveOutcome::GameOver
+                                    }
+                                    LobbyState::Playing {
+                                        board,
+                                        sessions,
+                                        moves,
+                                    } => {
+                                        if Some(board.side_to_move()) == sessions.find(session) {
+                                            match board.try_play(chess_move) {
+                                                Ok(()) => {
+                                                    info!("client played move, broadcasting");
+                                                    moves.push(chess_move);
+                                                    lobby.last_activity = Instant::now();
+                                                    if let Some(clock) = &mut lobby.clock {
+                                                        clock.moved(!board.side_to_move());
+                                                    }
+                                                    lobby.takeback = None;
+                                                    _ = lobby
+                                                        .tx
+                                                        .send(ServerMsg::PlayedMove(chess_move));
+                                                    MoveOutcome::Played
+                                                }
+                                                Err(err) => {
+                                                    info!("client sent illegal move {chess_move} ({err}), ignoring");
+                                                    MoveOutcome::Illegal
+                                                }
+                                            }
+                                        } else {
+                                            info!("client sent move when it wasn't their turn, ignoring");
+                                            MoveOutcome::NotYourTurn
+                                        }
+                                    }
+                                    LobbyState::Waiting { .. } => {
+                                        info!("client sent PlayMove for lobby that is not started yet ({lobby_id}), ignoring");
+                                        MoveOutcome::NotStarted
+                                    }
+                                };
+                                if result == MoveOutcome::Played {
+                                    lobby.check_game_over(&ratings);
+                                }
+                                result
+                            }
+                            None => {
+                                info!("client sent PlayMove for non-existing lobby {lobby_id}, ignoring");
+                                MoveOutcome::UnknownLobby
+                            }
+                        };
+                        if let Some(outcome) = outcome {
+                            _ = outcome.send(result);
+                        }
+                    }
+                    PlayerAction::RequestTakeback { lobby_id, session } => {
+                        if let Some(lobby) = lobbies.lock().await.get_mut(&lobby_id) {
+                            if let Some(msg) = lobby.request_takeback(session) {
+                                _ = lobby.tx.send(msg);
+                            }
+                        }
+                    }
+                    PlayerAction::AnswerTakeback {
+                        lobby_id,
+                        session,
+                        accept,
+                    } => {
+                        if let Some(lobby) = lobbies.lock().await.get_mut(&lobby_id) {
+                            if let Some(msg) = lobby.answer_takeback(session, accept) {
+                                let reset = matches!(msg, ServerMsg::ResetPosition { .. });
+                                _ = lobby.tx.send(msg);
+                                if let Some(clocks) = lobby.clocks().filter(|_| reset) {
+                                    _ = lobby.tx.send(clocks);
+                                }
+                            }
+                        }
+                    }
+                    PlayerAction::OfferRematch { lobby_id, session } => {
+                        let mut lobbies = lobbies.lock().await;
+                        let Some(lobby) = lobbies.get_mut(&lobby_id) else {
+                            continue;
+                        };
+                        match lobby.offer_rematch(session) {
+                            Some(RematchOffer::Offered(color)) => {
+                                _ = lobby.tx.send(ServerMsg::RematchOffered(color));
+                            }
+                            Some(RematchOffer::Accepted(rematch)) => {
+                                let id = Uuid::new_v4();
+                                info!("starting rematch of lobby {lobby_id} in lobby {id}");
+                                _ = lobby.tx.send(ServerMsg::Rematch { lobby_id: id });
+                                lobbies.insert(id, *rematch);
+                            }
+                            None => {}
+                        }
+                    }
+                }
+            }
+        });
+        tx
+    };
+
+    let state = Arc::new(AppState {
+        lobbies,
+        tx,
+        bots,
+        accounts,
+        ratings,
+        matchmaking: Mutex::default(),
+        lobby_feed: broadcast::channel(64).0,
+        config,
+    });
+
+    {
+        let state = Arc::clone(&state);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                state.lobbies.lock().await.retain(|&id, lobby| {
+                    let Some(reason) = lobby.expiry() else {
+                        return true;
+                    };
+                    info!("closing lobby {id}: {reason}");
+                    if lobby.is_listed() {
+                        _ = state.lobby_feed.send(ServerMsg::LobbyClosed { id });
+                    }
+                    false
+                });
+            }
+        });
+    }
+
+    {
+        let state = Arc::clone(&state);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(CLOCK_CHECK_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                let mut lobbies = state.lobbies.lock().await;
+                // finished games and games without a clock have nothing to check
+                let running = lobbies
+                    .iter_mut()
+                    .filter(|(_, lobby)| lobby.game_over.is_none() && lobby.clock.is_some());
+                for (id, lobby) in running {
+                    if lobby.out_of_time() {
+                        info!("player ran out of time in lobby {id}");
+                        lobby.check_game_over(&state.ratings);
+                    }
+                }
+            }
+        });
+    }
+
+    Ok(Router::new()
+        .route("/api/host", routing::post(host_game))
+        .route("/api/join/:id", routing::post(join_game))
+        .route("/api/seek", routing::post(seek_game))
+        .route("/api/lobbies", routing::get(list_lobbies))
+        .route("/api/leaderboard/:category", routing::get(leaderboard))
+        .route("/api/game/:id", routing::get(game_record))
+        .route("/api/game/:id/pgn", routing::get(game_pgn))
+        .nest("/api/account", accounts::routes())
+        .nest("/api/bot", bot_api::routes())
+        .route("/ws", routing::get(websocket_handler))
+        .nest_service(
+            "/",
+            get_service(ServeDir::new("./assets").fallback(ServeFile::new("./assets/index.html"))),
+        )
+        .with_state(state))
+}
+
+async fn websocket_handler(
+    ws: WebSocketUpgrade,
+    Query(params): Query<ConnectParams>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    ws.max_message_size(Limits::default().max_message_len)
+        .on_upgrade(move |socket| websocket(socket, state, params))
+}
+
+// This function deals with a single websocket connection, i.e., a single
+// connected client / user, for which we will spawn two independent tasks (for
+// receiving / sending chat messages).
+async fn websocket(mut socket: WebSocket, state: Arc<AppState>, params: ConnectParams) {
+    let (lobby_id, session, encoding) = if let Some(msg) = socket.recv().await {
+        match msg {
+            Ok(msg) => {
+                let encoding = match params.encoding {
+                    Some(EncodingParam::Binary) => Encoding::Binary(params.version),
+                    Some(EncodingParam::Json) => Encoding::Json,
+                    None => match Encoding::detect_axum(&msg, params.version) {
+                        Some(encoding) => encoding,
+                        None => {
+                            debug!("client sent {msg:?} as first message, disconnecting");
+                            return;
+                        }
+                    },
+                };
+                let msg = match encoding.decode_axum(msg, Limits::default()) {
+                    Ok(msg) => msg,
+                    Err(err) => {
+                        debug!("error reading client msg: {err}");
+                        return;
+                    }
+                };
+                match msg {
+                    ClientMsg::Connect { lobby_id, session } => (lobby_id, session, encoding),
+                    ClientMsg::WaitForMatch { seek_id } => {
+                        wait_for_match(socket, &state, encoding, seek_id).await;
+                        return;
+                    }
+                    ClientMsg::WatchLobbies => {
+                        watch_lobbies(socket, &state, encoding).await;
+                        return;
+                    }
+                    _ => {
+                        let Ok(msg) = encoding.encode_axum(&ServerMsg::ConnectRequired) else {
+                            warn!(
+                                "failed to convert message to axum message: {:?}",
+                                ServerMsg::ConnectRequired
+                            );
+                            return;
+                        };
+                        _ = socket.send(msg).await;
+                        return;
+                    }
+                }
+            }
+            Err(err) => {
+                eprintln!("got error: {err}");
+                return;
+            }
+        }
+    } else {
+        eprintln!("received nothing");
+        return;
+    };
+
+    let tx = match state.lobbies.lock().await.get(&lobby_id) {
+        Some(lobby @ Lobby { state, tx, .. }) => {
+            match state {
+                LobbyState::Waiting { session: sess } => {
+                    if *sess == session {
+                        _ = socket
+                            .send(
+                                encoding
+                                    .encode_axum(&ServerMsg::Connected(
+                                        GameState::WaitingForOpponent,
+                                    ))
+                                    .unwrap(),
+                            )
+                            .await;
+                    } else {
+                        _ = socket
+                            .send(encoding.encode_axum(&ServerMsg::InvalidSession).unwrap())
+                            .await;
+                        return;
+                    }
+                }
+                LobbyState::Playing {
+                    board, sessions, ..
+                } => {
+                    if let Some(color) = sessions.find(session) {
+                        _ = socket
+                            .send(
+                                encoding
+                                    .encode_axum(&ServerMsg::Connected(GameState::Ingame {
+                                        fen: board.to_string(),
+                                        color,
+                                    }))
+                                    .unwrap(),
+                            )
+                            .await;
+                        for msg in lobby.players().into_iter().chain(lobby.clocks()) {
+                            _ = socket.send(encoding.encode_axum(&msg).unwrap()).await;
+                        }
+                    } else {
+                        _ = socket
+                            .send(encoding.encode_axum(&ServerMsg::InvalidSession).unwrap())
+                            .await;
+                        return;
+                    }
+                }
+            }
+            tx.clone()
+        }
+        None => {
+            _ = socket
+                .send(encoding.encode_axum(&ServerMsg::InvalidLobby).unwrap())
+                .await;
+            return;
+        }
+    };
+
+    let mut rx = tx.subscribe();
+
+    // By splitting, we can send and receive at the same time.
+    let (mut sender, mut receiver) = socket.split();
+
+    // Spawn the first task that will receive broadcast messages and send text
+    // messages over the websocket to our client.
+    let mut send_task = {
+        let lobbies = Arc::clone(&state.lobbies);
+        tokio::spawn(async move {
+            loop {
+                let msgs = match rx.recv().await {
+                    Ok(msg) => vec![msg],
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("client in lobby {lobby_id} missed {skipped} messages, resyncing");
+                        match lobbies.lock().await.get(&lobby_id) {
+                            Some(lobby) => lobby.resync(session),
+                            None => break,
+                        }
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+                for msg in msgs {
+                    // In any websocket error, break loop.
+                    let msg = match encoding.encode_axum(&msg) {
+                        Ok(msg) => msg,
+                        Err(err) => {
+                            error!("failed to convert message to axum message: {err:?}");
+                            return;
+                        }
+                    };
+                    if sender.send(msg).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        })
+    };
+
+    let mut recv_task = {
+        let mut tx = state.tx.clone();
+        tokio::spawn(async move {
+            while let Some(Ok(msg)) = receiver.next().await {
+                let msg = encoding.decode_axum::<ClientMsg>(msg, Limits::default());
+                match msg {
+                    Ok(msg) => match msg {
+                        ClientMsg::PlayMove(chess_move) => {
+                            tx.send(PlayerAction::PlayMove {
+                                lobby_id,
+                                session,
+                                chess_move,
+                                outcome: None,
+                            })
+                            .await
+                            .unwrap();
+                        }
+                        ClientMsg::RequestTakeback => {
+                            tx.send(PlayerAction::RequestTakeback { lobby_id, session })
+                                .await
+                                .unwrap();
+                        }
+                        msg @ (ClientMsg::AcceptTakeback | ClientMsg::DeclineTakeback) => {
+                            tx.send(PlayerAction::AnswerTakeback {
+                                lobby_id,
+                                session,
+                                accept: msg == ClientMsg::AcceptTakeback,
+                            })
+                            .await
+                            .unwrap();
+                        }
+                        ClientMsg::OfferRematch => {
+                            tx.send(PlayerAction::OfferRematch { lobby_id, session })
+                                .await
+                                .unwrap();
+                        }
+                        msg => {
+                            debug!("client sent {msg:?} while already in game, ignoring");
+                        }
+                    },
+                    Err(err) => {
+                        info!("error deserializing client message: {err}");
+                        // client is clearly drunk, disconnect
+                        break;
+                    }
+                }
+            }
+        })
+    };
+    info!("user joined");
+
+    // If any one of the tasks run to completion, we abort the other.
+    tokio::select! {
+        _ = (&mut send_task) => recv_task.abort(),
+        _ = (&mut recv_task) => send_task.abort(),
+    };
+
+    // User left
+    info!("user left");
+
+    // nobody can join a public lobby whose host left, so stop listing it
+    let mut lobbies = state.lobbies.lock().await;
+    if let Some(lobby) = lobbies.get(&lobby_id) {
+        if lobby.is_listed()
+            && matches!(lobby.state, LobbyState::Waiting { session: host } if host == session)
+        {
+            lobbies.remove(&lobby_id);
+            _ = state
+                .lobby_feed
+                .send(ServerMsg::LobbyClosed { id: lobby_id });
+        }
+    }
+}
+
+async fn host_game(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    request: Option<Json<HostRequest>>,
+) -> Result<Json<HostResponse>, StatusCode> {
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+    let start = match &request.fen {
+        // ratings only make sense for games from the initial position
+        Some(_) if request.settings.rated => return Err(StatusCode::BAD_REQUEST),
+        Some(fen) => start_position(fen).ok_or(StatusCode::BAD_REQUEST)?,
+        None => Board::default(),
+    };
+    if !valid_settings(&request.settings) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if let Some(bot) = request.bot {
+        if request.settings.rated {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        return host_bot_game(state, user.into(), bot, request.settings, start).await;
+    }
+    if request.settings.rated && user.is_none() {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    Ok(Json(
+        create_lobby(&state, user.into(), request.settings, start).await,
+    ))
+}
+
+/// Parses a custom start position, games need to have at least one move left
+fn start_position(fen: &str) -> Option<Board> {
+    let board: Board = fen.trim().parse().ok()?;
+    (board.status() == GameStatus::Ongoing).then_some(board)
+}
+
+/// Rated games need a time control to pick the rating category
+fn valid_settings(settings: &LobbySettings) -> bool {
+    match settings.time_control {
+        Some(time_control) => time_control.is_valid(),
+        None => !settings.rated,
+    }
+}
+
+/// Creates a lobby waiting for an opponent.
+async fn create_lobby(
+    state: &AppState,
+    host: Player,
+    settings: LobbySettings,
+    start: Board,
+) -> HostResponse {
+    let lobby_code = Uuid::new_v4();
+    let session = Uuid::new_v4();
+    let mut lobby = Lobby::new(LobbyState::Waiting { session }, settings);
+    lobby.start = start;
+    lobby.players.insert(session, host);
+    let mut lobbies = state.lobbies.lock().await;
+    if lobby.is_listed() {
+        _ = state.lobby_feed.send(lobby_opened(lobby.info(lobby_code)));
+    }
+    lobbies.insert(lobby_code, lobby);
+
+    HostResponse {
+        lobby_id: lobby_code,
+        session,
+    }
+}
+
+async fn join_game(
+    Path(id): Path<Uuid>,
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+) -> Result<Json<JoinResponse>, StatusCode> {
+    join_lobby(&state, id, user.into()).await.map(Json)
+}
+
+/// Takes the free seat of a waiting lobby and starts the game.
+async fn join_lobby(
+    state: &AppState,
+    id: Uuid,
+    player: Player,
+) -> Result<JoinResponse, StatusCode> {
+    match state.lobbies.lock().await.get_mut(&id) {
+        Some(lobby) => match lobby.state {
+            LobbyState::Waiting { session } => {
+                if lobby.settings.rated {
+                    let host = lobby
+                        .players
+                        .get(&session)
+                        .and_then(|host| host.username.as_ref());
+                    // guests can't play rated games and nobody can play against themselves
+                    if player.username.is_none() || player.username.as_ref() == host {
+                        return Err(StatusCode::FORBIDDEN);
+                    }
+                }
+                let other = Uuid::new_v4();
+                let sessions = if rand::random() {
+                    Sessions {
+                        white: session,
+                        black: other,
+                    }
+                } else {
+                    Sessions {
+                        white: other,
+                        black: session,
+                    }
+                };
+                _ = lobby.tx.send(ServerMsg::OpponentJoined);
+                if lobby.is_listed() {
+                    _ = state.lobby_feed.send(ServerMsg::LobbyClosed { id });
+                }
+                lobby.state = LobbyState::playing(sessions, lobby.start.clone());
+                lobby.last_activity = Instant::now();
+                lobby.players.insert(other, player);
+                Ok(JoinResponse {
+                    lobby_id: id,
+                    session: other,
+                })
+            }
+            LobbyState::Playing { .. } => Err(StatusCode::CONFLICT),
+        },
+        None => Err(StatusCode::NOT_FOUND),
+    }
+}
+
+/// Hosts a game against an engine, which takes the second seat right away.
+///
+/// The configured UCI engine is used unless a level for the built-in engine is requested.
+async fn host_bot_game(
+    state: Arc<AppState>,
+    host: Player,
+    bot: BotSettings,
+    settings: LobbySettings,
+    start: Board,
+) -> Result<Json<HostResponse>, StatusCode> {
+    let bot = match (&state.config.engine, bot.level) {
+        (Some(path), None) => {
+            let move_time = bot
+                .move_time
+                .map(Duration::from_millis)
+                .unwrap_or(state.config.engine_move_time)
+                .min(MAX_ENGINE_MOVE_TIME);
+            Bot::uci(path, move_time).await.map_err(|err| {
+                error!("failed to start engine {}: {err}", path.display());
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?
+        }
+        (_, level) => Bot::builtin(level.unwrap_or(engine::Limits::DEFAULT_LEVEL)),
+    };
+
+    let lobby_id = Uuid::new_v4();
+    let session = Uuid::new_v4();
+    let bot_session = Uuid::new_v4();
+    let (sessions, bot_color) = if rand::random() {
+        (
+            Sessions {
+                white: session,
+                black: bot_session,
+            },
+            Color::Black,
+        )
+    } else {
+        (
+            Sessions {
+                white: bot_session,
+                black: session,
+            },
+            Color::White,
+        )
+    };
+    let mut lobby = Lobby::new(LobbyState::playing(sessions, start.clone()), settings);
+    lobby.start = start.clone();
+    lobby.players.insert(session, host);
+    lobby.players.insert(bot_session, Player::computer());
+    let rx = lobby.tx.subscribe();
+    state.lobbies.lock().await.insert(lobby_id, lobby);
+    tokio::spawn(play_bot(
+        bot,
+        start,
+        Arc::clone(&state),
+        rx,
+        lobby_id,
+        bot_session,
+        bot_color,
+    ));
+
+    Ok(Json(HostResponse { lobby_id, session }))
+}
+
+/// Plays the bot's moves in a lobby until the game is over, accepting every takeback.
+///
+/// Moves go through the [`PlayerAction`] channel like moves of any other player. If the bot
+/// falls behind the lobby's broadcasts, it continues from the lobby's current position.
+async fn play_bot(
+    mut bot: Bot,
+    start: Board,
+    state: Arc<AppState>,
+    mut rx: broadcast::Receiver<ServerMsg>,
+    lobby_id: Uuid,
+    session: Uuid,
+    color: Color,
+) {
+    let mut tx = state.tx.clone();
+    let fen = start.to_string();
+    let mut board = start;
+    let mut moves = Vec::new();
+    'game: while board.status() == GameStatus::Ongoing {
+        if board.side_to_move() == color {
+            let chess_move = match bot.best_move(&board, &fen, &moves).await {
+                Ok(chess_move) => chess_move,
+                Err(err) => {
+                    warn!("bot failed in lobby {lobby_id}: {err}");
+                    return;
+                }
+            };
+            if tx
+                .send(PlayerAction::PlayMove {
+                    lobby_id,
+                    session,
+                    chess_move,
+                    outcome: None,
+                })
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+
+        loop {
+            match rx.recv().await {
+                Ok(ServerMsg::PlayedMove(chess_move)) => {
+                    // the move was already validated when it was played
+                    moves.push(notation::to_uci(&board, chess_move));
+                    board.play_unchecked(chess_move);
+                    break;
+                }
+                Ok(ServerMsg::TakebackRequested(requester)) if requester != color => {
+                    // bots don't mind
+                    if tx
+                        .send(PlayerAction::AnswerTakeback {
+                            lobby_id,
+                            session,
+                            accept: true,
+                        })
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+                // games can also end without a move, when a player runs out of time
+                Ok(ServerMsg::GameOver { .. }) => break 'game,
+                Ok(ServerMsg::ResetPosition { fen, plies }) => {
+                    let Ok(position) = fen.parse() else {
+                        warn!("bot in lobby {lobby_id} received invalid position {fen}, stopping");
+                        return;
+                    };
+                    board = position;
+                    moves.truncate(moves.len().saturating_sub(plies));
+                    break;
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("bot in lobby {lobby_id} missed {skipped} messages, resyncing");
+                    let lobbies = state.lobbies.lock().await;
+                    let Some(lobby) = lobbies
+                        .get(&lobby_id)
+                        .filter(|lobby| lobby.game_over.is_none())
+                    else {
+                        return;
+                    };
+                    let (LobbyState::Playing { board: current, .. }, Some(record)) =
+                        (&lobby.state, lobby.record())
+                    else {
+                        return;
+                    };
+                    board = current.clone();
+                    moves = record.moves;
+                    break;
+                }
+                Err(RecvError::Closed) => return,
+            }
+        }
+    }
+    debug!("game in lobby {lobby_id} is over, stopping bot");
+}
+
+async fn seek_game(
+    State(state): State<Arc<AppState>>,
+    CurrentUser(user): CurrentUser,
+    Json(request): Json<SeekRequest>,
+) -> Result<Json<SeekResponse>, StatusCode> {
+    if !request.time_control.is_valid()
+        || request
+            .rating_range
+            .is_some_and(|range| range.min > range.max)
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let rating = match &user {
+        Some(user) => state
+            .ratings
+            .get(request.time_control.category(), &user.username)
+            .map_err(|err| {
+                error!("failed to read rating: {err}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?
+            .rounded(),
+        None if request.rated => return Err(StatusCode::UNAUTHORIZED),
+        None => matchmaking::DEFAULT_RATING,
+    };
+    let seek_id = state
+        .matchmaking
+        .lock()
+        .await
+        .seek(request, user.into(), rating);
+    debug!("new seek {seek_id} for {}", request.time_control);
+    Ok(Json(SeekResponse { seek_id }))
+}
+
+/// Keeps a seek alive until it gets paired, or cancels it once the client disconnects.
+async fn wait_for_match(
+    mut socket: WebSocket,
+    state: &AppState,
+    encoding: Encoding,
+    seek_id: Uuid,
+) {
+    let (notify, matched) = oneshot::channel();
+    let pairing = state.matchmaking.lock().await.wait(seek_id, notify);
+    match pairing {
+        Ok(Some(pairing)) => start_matched_game(state, pairing).await,
+        Ok(None) => {}
+        Err(_) => {
+            _ = socket
+                .send(encoding.encode_axum(&ServerMsg::InvalidSeek).unwrap())
+                .await;
+            return;
+        }
+    }
+
+    let msg = tokio::select! {
+        result = matched => match result {
+            Ok((lobby_id, session)) => ServerMsg::Matched { lobby_id, session },
+            Err(_) => return,
+        },
+        _ = async { while let Some(Ok(_)) = socket.recv().await {} } => {
+            debug!("client stopped waiting for seek {seek_id}");
+            state.matchmaking.lock().await.cancel(seek_id);
+            return;
+        }
+    };
+    _ = socket.send(encoding.encode_axum(&msg).unwrap()).await;
+}
+
+/// Creates the game of two paired seeks and tells both clients where to connect.
+async fn start_matched_game(state: &AppState, pairing: Pairing) {
+    let lobby_id = Uuid::new_v4();
+    let sessions = Sessions {
+        white: Uuid::new_v4(),
+        black: Uuid::new_v4(),
+    };
+    let settings = LobbySettings {
+        time_control: Some(pairing.time_control),
+        rated: pairing.rated,
+        ..LobbySettings::default()
+    };
+    let [first, second] = pairing.players;
+    let (white, black) = if rand::random() {
+        (first, second)
+    } else {
+        (second, first)
+    };
+    let mut lobby = Lobby::new(LobbyState::playing(sessions, Board::default()), settings);
+    lobby.players.insert(sessions.white, white.player);
+    lobby.players.insert(sessions.black, black.player);
+    state.lobbies.lock().await.insert(lobby_id, lobby);
+    info!(
+        "paired seeks for {}, starting game {lobby_id}",
+        pairing.time_control
+    );
+    _ = white.notify.send((lobby_id, sessions.white));
+    _ = black.notify.send((lobby_id, sessions.black));
+}
+
+fn lobby_opened(info: LobbyInfo) -> ServerMsg {
+    ServerMsg::LobbyOpened {
+        id: info.id,
+        time_control: info.time_control,
+        variant: info.variant,
+        rated: info.rated,
+    }
+}
+
+/// Public lobbies waiting for an opponent, oldest first
+fn listed_lobbies(lobbies: &HashMap<Uuid, Lobby>) -> Vec<LobbyInfo> {
+    let mut listed: Vec<_> = lobbies
+        .iter()
+        .filter(|(_, lobby)| lobby.is_listed())
+        .collect();
+    listed.sort_by_key(|(_, lobby)| lobby.created);
+    listed
+        .into_iter()
+        .map(|(&id, lobby)| lobby.info(id))
+        .collect()
+}
+
+async fn list_lobbies(State(state): State<Arc<AppState>>) -> Json<Vec<LobbyInfo>> {
+    Json(listed_lobbies(&*state.lobbies.lock().await))
+}
+
+/// Sends all public lobbies and then every change to the list until the client disconnects.
+async fn watch_lobbies(mut socket: WebSocket, state: &AppState, encoding: Encoding) {
+    // subscribe before taking the snapshot so no change gets lost, changes are sent while
+    // holding the lobby lock
+    let mut feed = state.lobby_feed.subscribe();
+    let snapshot = listed_lobbies(&*state.lobbies.lock().await);
+    for info in snapshot {
+        let msg = encoding.encode_axum(&lobby_opened(info)).unwrap();
+        if socket.send(msg).await.is_err() {
+            return;
+        }
+    }
+    loop {
+        tokio::select! {
+            msg = feed.recv() => match msg {
+                Ok(msg) => {
+                    if socket.send(encoding.encode_axum(&msg).unwrap()).await.is_err() {
+                        return;
+                    }
+                }
+                Err(err) => {
+                    debug!("lobby feed failed ({err}), disconnecting watcher");
+                    return;
+                }
+            },
+            msg = socket.recv() => {
+                if !matches!(msg, Some(Ok(_))) {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+async fn leaderboard(
+    Path(category): Path<RatingCategory>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<LeaderboardEntry>>, StatusCode> {
+    let internal_error = |err: sled::Error| {
+        error!("failed to read leaderboard: {err}");
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    let mut ratings = state.ratings.category(category).map_err(internal_error)?;
+    ratings.sort_by(|(_, a), (_, b)| b.rating.total_cmp(&a.rating));
+    let mut entries = Vec::new();
+    for (username, rating) in ratings.into_iter().take(LEADERBOARD_SIZE) {
+        let Some(user) = state
+            .accounts
+            .user_info(&username)
+            .map_err(internal_error)?
+        else {
+            continue;
+        };
+        entries.push(LeaderboardEntry {
+            username: user.username,
+            display_name: user.display_name,
+            rating: rating.rounded(),
+            deviation: rating.deviation.round() as u32,
+            games: rating.games,
+        });
+    }
+    Ok(Json(entries))
+}
+
+async fn game_record(
+    Path(id): Path<Uuid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<GameRecord>, StatusCode> {
+    state
+        .lobbies
+        .lock()
+        .await
+        .get(&id)
+        .and_then(Lobby::record)
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Downloads a game as PGN, ongoing games end with the `*` result
+async fn game_pgn(
+    Path(id): Path<Uuid>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let pgn = state
+        .lobbies
+        .lock()
+        .await
+        .get(&id)
+        .and_then(Lobby::pgn)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-chess-pgn".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{id}.pgn\""),
+            ),
+        ],
+        pgn,
+    ))
+}
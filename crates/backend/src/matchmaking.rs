@@ -0,0 +1,334 @@
+//! In-memory matchmaking, pairing seeks with the same time control.
+
+use crate::Player;
+use common::http::{RatingRange, SeekRequest, TimeControl};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+/// Rating assumed for guests
+pub const DEFAULT_RATING: u32 = 1500;
+
+/// Seeks nobody waits for are dropped after this long
+const UNCLAIMED_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Notifies a waiting client of the lobby and session of its game
+pub type Notify = oneshot::Sender<(Uuid, Uuid)>;
+
+struct Seek {
+    id: Uuid,
+    player: Player,
+    rating: u32,
+    rating_range: Option<RatingRange>,
+    rated: bool,
+    created: Instant,
+    /// Set once a client waits for the seek over a WebSocket, only then it can be paired
+    waiting: Option<Notify>,
+}
+
+impl Seek {
+    fn accepts(&self, other: &Seek) -> bool {
+        // the same account seeking from two tabs mustn't play against itself
+        let same_account =
+            self.player.username.is_some() && self.player.username == other.player.username;
+        !same_account
+            && self.rated == other.rated
+            && self
+                .rating_range
+                .is_none_or(|range| range.contains(other.rating))
+    }
+}
+
+/// Two paired seeks, the caller creates the game and notifies both clients
+pub struct Pairing {
+    pub time_control: TimeControl,
+    pub rated: bool,
+    pub players: [Seeker; 2],
+}
+
+pub struct Seeker {
+    pub player: Player,
+    pub notify: Notify,
+}
+
+#[derive(Default)]
+pub struct Matchmaking {
+    pools: HashMap<TimeControl, Vec<Seek>>,
+}
+
+impl Matchmaking {
+    pub fn seek(&mut self, request: SeekRequest, player: Player, rating: u32) -> Uuid {
+        self.expire();
+        let id = Uuid::new_v4();
+        self.pools
+            .entry(request.time_control)
+            .or_default()
+            .push(Seek {
+                id,
+                player,
+                rating,
+                rating_range: request.rating_range,
+                rated: request.rated,
+                created: Instant::now(),
+                waiting: None,
+            });
+        id
+    }
+
+    /// Marks a seek as waiting and pairs it with the oldest compatible waiting seek.
+    ///
+    /// Returns the notifier back if the seek doesn't exist.
+    pub fn wait(&mut self, id: Uuid, notify: Notify) -> Result<Option<Pairing>, Notify> {
+        let Some((&time_control, pool)) = self
+            .pools
+            .iter_mut()
+            .find(|(_, pool)| pool.iter().any(|seek| seek.id == id))
+        else {
+            return Err(notify);
+        };
+        let index = pool.iter().position(|seek| seek.id == id).unwrap();
+        pool[index].waiting = Some(notify);
+
+        let seek = &pool[index];
+        let Some(other) = pool.iter().position(|other| {
+            other.id != id
+                && other
+                    .waiting
+                    .as_ref()
+                    .is_some_and(|notify| !notify.is_closed())
+                && seek.accepts(other)
+                && other.accepts(seek)
+        }) else {
+            return Ok(None);
+        };
+        // remove the later index first so the earlier one stays valid
+        let (first, second) = (index.min(other), index.max(other));
+        let second = pool.remove(second);
+        let first = pool.remove(first);
+        if pool.is_empty() {
+            self.pools.remove(&time_control);
+        }
+        Ok(Some(Pairing {
+            time_control,
+            rated: first.rated,
+            players: [first, second].map(|seek| Seeker {
+                player: seek.player,
+                notify: seek.waiting.unwrap(),
+            }),
+        }))
+    }
+
+    pub fn cancel(&mut self, id: Uuid) {
+        for pool in self.pools.values_mut() {
+            pool.retain(|seek| seek.id != id);
+        }
+        self.pools.retain(|_, pool| !pool.is_empty());
+    }
+
+    /// Drops seeks that were never waited for, e.g. because the client went away.
+    fn expire(&mut self) {
+        for pool in self.pools.values_mut() {
+            pool.retain(|seek| {
+                seek.waiting.is_some() || seek.created.elapsed() < UNCLAIMED_TIMEOUT
+            });
+        }
+        self.pools.retain(|_, pool| !pool.is_empty());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLITZ: TimeControl = TimeControl {
+        initial: 300,
+        increment: 0,
+    };
+
+    fn request(time_control: TimeControl, rated: bool) -> SeekRequest {
+        SeekRequest {
+            time_control,
+            rating_range: None,
+            rated,
+        }
+    }
+
+    fn player(username: &str) -> Player {
+        Player {
+            display_name: username.to_string(),
+            username: Some(username.to_string()),
+        }
+    }
+
+    /// Matchmaking with clients that keep waiting for their seeks
+    #[derive(Default)]
+    struct Clients {
+        inner: Matchmaking,
+        matched: Vec<oneshot::Receiver<(Uuid, Uuid)>>,
+    }
+
+    impl Clients {
+        /// Creates a seek and waits for it, returning the pairing if one was made.
+        fn seek_and_wait(
+            &mut self,
+            request: SeekRequest,
+            player: Player,
+            rating: u32,
+        ) -> Option<Pairing> {
+            let id = self.inner.seek(request, player, rating);
+            let (notify, matched) = oneshot::channel();
+            self.matched.push(matched);
+            self.inner.wait(id, notify).ok().unwrap()
+        }
+    }
+
+    fn usernames(pairing: &Pairing) -> [Option<&str>; 2] {
+        [0, 1].map(|i| pairing.players[i].player.username.as_deref())
+    }
+
+    #[test]
+    fn pairs_compatible_seeks() {
+        let mut matchmaking = Clients::default();
+        let request = request(BLITZ, false);
+        assert!(matchmaking
+            .seek_and_wait(request, player("a"), 1500)
+            .is_none());
+        let pairing = matchmaking
+            .seek_and_wait(request, player("b"), 1500)
+            .unwrap();
+        assert_eq!(pairing.time_control, BLITZ);
+        assert!(!pairing.rated);
+        assert_eq!(usernames(&pairing), [Some("a"), Some("b")]);
+        assert!(matchmaking.inner.pools.is_empty());
+    }
+
+    #[test]
+    fn only_waiting_seeks_are_paired() {
+        let mut matchmaking = Clients::default();
+        let request = request(BLITZ, false);
+        matchmaking.inner.seek(request, player("a"), 1500);
+        assert!(matchmaking
+            .seek_and_wait(request, player("b"), 1500)
+            .is_none());
+    }
+
+    #[test]
+    fn time_controls_have_separate_pools() {
+        let mut matchmaking = Clients::default();
+        let rapid = TimeControl {
+            initial: 600,
+            increment: 5,
+        };
+        assert!(matchmaking
+            .seek_and_wait(request(BLITZ, false), player("a"), 1500)
+            .is_none());
+        assert!(matchmaking
+            .seek_and_wait(request(rapid, false), player("b"), 1500)
+            .is_none());
+        let pairing = matchmaking
+            .seek_and_wait(request(rapid, false), player("c"), 1500)
+            .unwrap();
+        assert_eq!(pairing.time_control, rapid);
+        assert_eq!(usernames(&pairing), [Some("b"), Some("c")]);
+    }
+
+    #[test]
+    fn rated_seeks_only_meet_rated_seeks() {
+        let mut matchmaking = Clients::default();
+        assert!(matchmaking
+            .seek_and_wait(request(BLITZ, true), player("a"), 1500)
+            .is_none());
+        assert!(matchmaking
+            .seek_and_wait(request(BLITZ, false), player("b"), 1500)
+            .is_none());
+        let pairing = matchmaking
+            .seek_and_wait(request(BLITZ, true), player("c"), 1500)
+            .unwrap();
+        assert!(pairing.rated);
+        assert_eq!(usernames(&pairing), [Some("a"), Some("c")]);
+    }
+
+    #[test]
+    fn rating_ranges_apply_both_ways() {
+        let mut matchmaking = Clients::default();
+        let narrow = SeekRequest {
+            rating_range: Some(RatingRange {
+                min: 1400,
+                max: 1600,
+            }),
+            ..request(BLITZ, false)
+        };
+        assert!(matchmaking
+            .seek_and_wait(narrow, player("a"), 1500)
+            .is_none());
+        // out of a's range
+        assert!(matchmaking
+            .seek_and_wait(request(BLITZ, false), player("b"), 1800)
+            .is_none());
+        // a is out of c's range
+        let high = SeekRequest {
+            rating_range: Some(RatingRange {
+                min: 1700,
+                max: 2000,
+            }),
+            ..request(BLITZ, false)
+        };
+        let pairing = matchmaking.seek_and_wait(high, player("c"), 1550).unwrap();
+        assert_eq!(usernames(&pairing), [Some("b"), Some("c")]);
+        let pairing = matchmaking
+            .seek_and_wait(request(BLITZ, false), player("d"), 1600)
+            .unwrap();
+        assert_eq!(usernames(&pairing), [Some("a"), Some("d")]);
+    }
+
+    #[test]
+    fn never_pairs_an_account_with_itself() {
+        let mut matchmaking = Clients::default();
+        let request = request(BLITZ, false);
+        assert!(matchmaking
+            .seek_and_wait(request, player("a"), 1500)
+            .is_none());
+        assert!(matchmaking
+            .seek_and_wait(request, player("a"), 1500)
+            .is_none());
+        let pairing = matchmaking
+            .seek_and_wait(request, player("b"), 1500)
+            .unwrap();
+        assert_eq!(usernames(&pairing), [Some("a"), Some("b")]);
+
+        // guests have no account, so any two of them can play
+        let mut matchmaking = Clients::default();
+        assert!(matchmaking
+            .seek_and_wait(request, Player::guest(), 1500)
+            .is_none());
+        assert!(matchmaking
+            .seek_and_wait(request, Player::guest(), 1500)
+            .is_some());
+    }
+
+    #[test]
+    fn closed_waiters_are_skipped() {
+        let mut matchmaking = Clients::default();
+        let request = request(BLITZ, false);
+        let id = matchmaking.inner.seek(request, player("a"), 1500);
+        let (notify, matched) = oneshot::channel();
+        assert!(matchmaking.inner.wait(id, notify).unwrap().is_none());
+        drop(matched);
+        assert!(matchmaking
+            .seek_and_wait(request, player("b"), 1500)
+            .is_none());
+        matchmaking.inner.cancel(id);
+        assert!(matchmaking
+            .seek_and_wait(request, player("c"), 1500)
+            .is_some());
+    }
+
+    #[test]
+    fn unknown_seeks_are_rejected() {
+        let (notify, _matched) = oneshot::channel();
+        assert!(Matchmaking::default().wait(Uuid::new_v4(), notify).is_err());
+    }
+}
@@ -0,0 +1,247 @@
+//! Glicko-2 ratings of registered users, one per [`RatingCategory`].
+//!
+//! Every rated game is treated as its own rating period, see
+//! <http://www.glicko.net/glicko/glicko2.pdf> for the algorithm.
+
+use common::http::RatingCategory;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+/// Converts between the Glicko and the Glicko-2 scale
+const SCALE: f64 = 173.7178;
+/// Constrains how much the volatility changes per game
+const TAU: f64 = 0.5;
+const CONVERGENCE_TOLERANCE: f64 = 0.000_001;
+const MIN_DEVIATION: f64 = 45.0;
+const MAX_DEVIATION: f64 = 350.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Rating {
+    pub rating: f64,
+    pub deviation: f64,
+    pub volatility: f64,
+    pub games: u32,
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Self {
+            rating: 1500.0,
+            deviation: MAX_DEVIATION,
+            volatility: 0.06,
+            games: 0,
+        }
+    }
+}
+
+impl Rating {
+    /// Returns the rating after a game against `opponent`, `score` is 1 for a win, 0.5 for a
+    /// draw and 0 for a loss.
+    pub fn update(&self, opponent: &Rating, score: f64) -> Rating {
+        self.update_period(&[(*opponent, score)])
+    }
+
+    /// Returns the rating after a rating period consisting of games against opponents with
+    /// the given scores, steps 2 to 8 of the paper.
+    fn update_period(&self, games: &[(Rating, f64)]) -> Rating {
+        let mu = (self.rating - 1500.0) / SCALE;
+        let phi = self.deviation / SCALE;
+
+        let mut inverse_variance = 0.0;
+        let mut improvement = 0.0;
+        for (opponent, score) in games {
+            let opponent_mu = (opponent.rating - 1500.0) / SCALE;
+            let opponent_phi = opponent.deviation / SCALE;
+            let g = 1.0 / (1.0 + 3.0 * opponent_phi.powi(2) / PI.powi(2)).sqrt();
+            let expected = 1.0 / (1.0 + (-g * (mu - opponent_mu)).exp());
+            inverse_variance += g.powi(2) * expected * (1.0 - expected);
+            improvement += g * (score - expected);
+        }
+        let variance = 1.0 / inverse_variance;
+        let delta = variance * improvement;
+
+        let volatility = self.new_volatility(phi, variance, delta);
+        let phi_star = (phi.powi(2) + volatility.powi(2)).sqrt();
+        let new_phi = 1.0 / (1.0 / phi_star.powi(2) + 1.0 / variance).sqrt();
+        let new_mu = mu + new_phi.powi(2) * improvement;
+
+        Rating {
+            rating: new_mu * SCALE + 1500.0,
+            deviation: (new_phi * SCALE).clamp(MIN_DEVIATION, MAX_DEVIATION),
+            volatility,
+            games: self.games + games.len() as u32,
+        }
+    }
+
+    /// Step 5 of the paper, finds the new volatility with the Illinois algorithm.
+    fn new_volatility(&self, phi: f64, variance: f64, delta: f64) -> f64 {
+        let a = (self.volatility.powi(2)).ln();
+        let f = |x: f64| {
+            let ex = x.exp();
+            ex * (delta.powi(2) - phi.powi(2) - variance - ex)
+                / (2.0 * (phi.powi(2) + variance + ex).powi(2))
+                - (x - a) / TAU.powi(2)
+        };
+
+        let mut lower = a;
+        let mut upper = if delta.powi(2) > phi.powi(2) + variance {
+            (delta.powi(2) - phi.powi(2) - variance).ln()
+        } else {
+            let mut k = 1.0;
+            while f(a - k * TAU) < 0.0 {
+                k += 1.0;
+            }
+            a - k * TAU
+        };
+        let mut f_lower = f(lower);
+        let mut f_upper = f(upper);
+        while (upper - lower).abs() > CONVERGENCE_TOLERANCE {
+            let c = lower + (lower - upper) * f_lower / (f_upper - f_lower);
+            let f_c = f(c);
+            if f_c * f_upper <= 0.0 {
+                lower = upper;
+                f_lower = f_upper;
+            } else {
+                f_lower /= 2.0;
+            }
+            upper = c;
+            f_upper = f_c;
+        }
+        (lower / 2.0).exp()
+    }
+
+    /// Rating rounded for display
+    pub fn rounded(&self) -> u32 {
+        self.rating.round().max(0.0) as u32
+    }
+}
+
+/// Ratings by category and lowercase username
+#[derive(Clone)]
+pub struct Ratings {
+    tree: sled::Tree,
+}
+
+impl Ratings {
+    pub fn open(db: &sled::Db) -> sled::Result<Self> {
+        Ok(Self {
+            tree: db.open_tree("ratings")?,
+        })
+    }
+
+    fn key(category: RatingCategory, username: &str) -> String {
+        format!("{category}/{}", username.to_lowercase())
+    }
+
+    /// Rating of a user, users who haven't played in the category yet get the default rating.
+    pub fn get(&self, category: RatingCategory, username: &str) -> sled::Result<Rating> {
+        Ok(self
+            .tree
+            .get(Self::key(category, username))?
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default())
+    }
+
+    pub fn set(
+        &self,
+        category: RatingCategory,
+        username: &str,
+        rating: &Rating,
+    ) -> sled::Result<()> {
+        let bytes = serde_json::to_vec(rating).expect("ratings are serializable");
+        self.tree.insert(Self::key(category, username), bytes)?;
+        Ok(())
+    }
+
+    /// All rated users of a category by lowercase username
+    pub fn category(&self, category: RatingCategory) -> sled::Result<Vec<(String, Rating)>> {
+        let prefix = format!("{category}/");
+        self.tree
+            .scan_prefix(&prefix)
+            .filter_map(|entry| match entry {
+                Ok((key, bytes)) => {
+                    let username = String::from_utf8_lossy(&key[prefix.len()..]).into_owned();
+                    let rating = serde_json::from_slice(&bytes).ok()?;
+                    Some(Ok((username, rating)))
+                }
+                Err(err) => Some(Err(err)),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rating(rating: f64, deviation: f64) -> Rating {
+        Rating {
+            rating,
+            deviation,
+            ..Rating::default()
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() < tolerance,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn example_from_the_paper() {
+        let player = rating(1500.0, 200.0);
+        let after = player.update_period(&[
+            (rating(1400.0, 30.0), 1.0),
+            (rating(1550.0, 100.0), 0.0),
+            (rating(1700.0, 300.0), 0.0),
+        ]);
+        assert_close(after.rating, 1464.06, 0.01);
+        assert_close(after.deviation, 151.52, 0.01);
+        assert_close(after.volatility, 0.05999, 0.00001);
+        assert_eq!(after.games, 3);
+    }
+
+    #[test]
+    fn draws_between_equal_players_change_nothing_but_the_deviation() {
+        let player = rating(1600.0, 120.0);
+        let after = player.update(&player, 0.5);
+        assert_close(after.rating, player.rating, 1e-9);
+        assert!(after.deviation < player.deviation);
+    }
+
+    #[test]
+    fn results_are_symmetric() {
+        let (white, black) = (rating(1700.0, 80.0), rating(1500.0, 80.0));
+        for score in [0.0, 0.5, 1.0] {
+            let white_after = white.update(&black, score);
+            let black_after = black.update(&white, 1.0 - score);
+            // with equal deviations the deltas of both players sum to zero
+            assert_close(
+                white_after.rating - white.rating,
+                black.rating - black_after.rating,
+                1e-6,
+            );
+        }
+        // the favorite gains less from a win than it loses from a loss, and loses from a draw
+        let win = white.update(&black, 1.0).rating - white.rating;
+        let loss = white.update(&black, 0.0).rating - white.rating;
+        let draw = white.update(&black, 0.5).rating - white.rating;
+        assert!(win > 0.0 && loss < 0.0 && win < -loss);
+        assert!(draw < 0.0);
+        assert_close(black.update(&white, 0.5).rating - black.rating, -draw, 1e-6);
+    }
+
+    #[test]
+    fn deviation_stays_in_bounds() {
+        let mut player = Rating::default();
+        let opponent = Rating::default();
+        for game in 0..200 {
+            player = player.update(&opponent, f64::from(game % 2));
+            assert!((MIN_DEVIATION..=MAX_DEVIATION).contains(&player.deviation));
+        }
+        assert!(player.deviation < 100.0);
+        assert_eq!(player.games, 200);
+    }
+}
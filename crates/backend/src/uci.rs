@@ -0,0 +1,277 @@
+//! Minimal driver for local engines speaking the UCI protocol.
+
+use log::debug;
+use std::{
+    io::{self, ErrorKind},
+    path::Path,
+    process::Stdio,
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+    process::{Child, ChildStdin, ChildStdout, Command},
+    time::timeout,
+};
+
+/// How long the engine may take to answer anything on top of its thinking time, shorter in
+/// tests so the timeout tests don't take seconds
+const GRACE_PERIOD: Duration = if cfg!(test) {
+    Duration::from_secs(1)
+} else {
+    Duration::from_secs(5)
+};
+
+/// A running engine process, killed when dropped.
+pub struct Engine {
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+}
+
+impl Engine {
+    /// Starts the engine at `path` and waits until it is ready for a new game.
+    pub async fn spawn(path: &Path) -> io::Result<Self> {
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped")).lines();
+        let mut engine = Self {
+            _child: child,
+            stdin,
+            stdout,
+        };
+
+        engine.send("uci").await?;
+        engine.wait_for("uciok", Duration::ZERO).await?;
+        engine.send("ucinewgame").await?;
+        engine.send("isready").await?;
+        engine.wait_for("readyok", Duration::ZERO).await?;
+        Ok(engine)
+    }
+
+    /// Searches the position reached by playing the UCI `moves` from `fen` and returns
+    /// the best move in UCI notation.
+    pub async fn best_move(
+        &mut self,
+        fen: &str,
+        moves: &[String],
+        move_time: Duration,
+    ) -> io::Result<String> {
+        let mut position = format!("position fen {fen}");
+        if !moves.is_empty() {
+            position.push_str(" moves ");
+            position.push_str(&moves.join(" "));
+        }
+        self.send(&position).await?;
+        self.send(&format!("go movetime {}", move_time.as_millis()))
+            .await?;
+        let line = self.wait_for("bestmove", move_time).await?;
+        match line.split_whitespace().nth(1) {
+            Some("(none)") | None => Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("engine returned no move: '{line}'"),
+            )),
+            Some(best_move) => Ok(best_move.to_string()),
+        }
+    }
+
+    async fn send(&mut self, command: &str) -> io::Result<()> {
+        debug!("engine <- {command}");
+        self.stdin.write_all(command.as_bytes()).await?;
+        self.stdin.write_all(b"\n").await?;
+        self.stdin.flush().await
+    }
+
+    /// Reads lines until one starts with `token` and returns it.
+    async fn wait_for(&mut self, token: &str, time: Duration) -> io::Result<String> {
+        let read = async {
+            loop {
+                let Some(line) = self.stdout.next_line().await? else {
+                    return Err(io::Error::new(
+                        ErrorKind::UnexpectedEof,
+                        format!("engine exited while waiting for '{token}'"),
+                    ));
+                };
+                debug!("engine -> {line}");
+                if line.split_whitespace().next() == Some(token) {
+                    return Ok(line);
+                }
+            }
+        };
+        timeout(time + GRACE_PERIOD, read).await.map_err(|_| {
+            io::Error::new(
+                ErrorKind::TimedOut,
+                format!("engine did not send '{token}' in time"),
+            )
+        })?
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::{fs, os::unix::fs::PermissionsExt};
+    use tempfile::TempDir;
+
+    const FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    /// Fake engines by name with their shell commands answering `uci` and `go`, every
+    /// engine logs the commands it receives to `<name>.log`
+    const ENGINES: &[(&str, &str, &str)] = &[
+        (
+            "moves",
+            "echo 'id name Fake'; echo uciok",
+            "echo 'info depth 1 score cp 20'; echo 'bestmove e2e4 ponder e7e5'",
+        ),
+        ("start", "echo uciok", "echo 'bestmove g1f3'"),
+        ("no_move", "echo uciok", "echo 'bestmove (none)'"),
+        ("exits", "echo uciok", "exit 0"),
+        ("thinks_forever", "echo uciok", ":"),
+        ("no_handshake", ":", ":"),
+    ];
+
+    /// A fake engine script in its own temporary directory, removed when dropped
+    struct FakeEngine {
+        dir: TempDir,
+        name: &'static str,
+    }
+
+    impl FakeEngine {
+        /// Writes the script of the fake engine `name`, unknown names leave the directory
+        /// empty
+        fn new(name: &'static str) -> Self {
+            let dir = tempfile::Builder::new()
+                .prefix("fake-uci-")
+                .tempdir()
+                .unwrap();
+            if let Some((_, uci, go)) = ENGINES.iter().find(|(engine, ..)| *engine == name) {
+                let log = dir.path().join(format!("{name}.log"));
+                let script = format!(
+                    "#!/bin/sh
+while read -r line; do
+  echo \"$line\" >> '{}'
+  case \"$line\" in
+    uci) {uci} ;;
+    isready) echo readyok ;;
+    go*) {go} ;;
+  esac
+done
+",
+                    log.display()
+                );
+                let path = dir.path().join(name);
+                fs::write(&path, script).unwrap();
+                fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+            }
+            Self { dir, name }
+        }
+
+        /// Starts the engine. Tests writing their scripts in parallel can make executing
+        /// a script fail while another test's child process still holds it open for
+        /// writing between fork and exec, which is retried.
+        async fn spawn(&self) -> io::Result<Engine> {
+            let path = self.dir.path().join(self.name);
+            loop {
+                match Engine::spawn(&path).await {
+                    Err(err) if err.kind() == ErrorKind::ExecutableFileBusy => {
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+                    }
+                    result => return result,
+                }
+            }
+        }
+
+        fn log(&self) -> Vec<String> {
+            fs::read_to_string(self.dir.path().join(format!("{}.log", self.name)))
+                .unwrap()
+                .lines()
+                .map(str::to_string)
+                .collect()
+        }
+    }
+
+    #[tokio::test]
+    async fn plays_the_best_move() {
+        let fake = FakeEngine::new("moves");
+        let mut engine = fake.spawn().await.unwrap();
+        let moves = ["e2e4".to_string(), "e7e5".to_string()];
+        let best_move = engine
+            .best_move(FEN, &moves, Duration::from_millis(100))
+            .await
+            .unwrap();
+        assert_eq!(best_move, "e2e4");
+        assert_eq!(
+            fake.log(),
+            [
+                "uci",
+                "ucinewgame",
+                "isready",
+                &format!("position fen {FEN} moves e2e4 e7e5"),
+                "go movetime 100",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn searches_the_start_position() {
+        let fake = FakeEngine::new("start");
+        let mut engine = fake.spawn().await.unwrap();
+        let best_move = engine
+            .best_move(FEN, &[], Duration::from_millis(50))
+            .await
+            .unwrap();
+        assert_eq!(best_move, "g1f3");
+        assert!(fake.log().contains(&format!("position fen {FEN}")));
+    }
+
+    #[tokio::test]
+    async fn no_move_is_an_error() {
+        let fake = FakeEngine::new("no_move");
+        let mut engine = fake.spawn().await.unwrap();
+        let err = engine
+            .best_move(FEN, &[], Duration::ZERO)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn engine_exiting_is_an_error() {
+        let fake = FakeEngine::new("exits");
+        let mut engine = fake.spawn().await.unwrap();
+        let err = engine
+            .best_move(FEN, &[], Duration::ZERO)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[tokio::test]
+    async fn search_times_out() {
+        let fake = FakeEngine::new("thinks_forever");
+        let mut engine = fake.spawn().await.unwrap();
+        let err = engine
+            .best_move(FEN, &[], Duration::ZERO)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+    }
+
+    #[tokio::test]
+    async fn handshake_times_out() {
+        let fake = FakeEngine::new("no_handshake");
+        let err = fake.spawn().await.err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+    }
+
+    #[tokio::test]
+    async fn missing_engine_fails_to_start() {
+        let fake = FakeEngine::new("missing");
+        let err = fake.spawn().await.err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,66 @@
+//! Hosts a game and plays random legal moves against whoever joins.
+//!
+//! ```
+//! cargo r -p client --example random_bot -- http://localhost:3000
+//! ```
+
+use client::Client;
+use common::ws::{GameState, ServerMsg};
+use cozy_chess::{Board, Color};
+use futures::StreamExt;
+use log::info;
+use rand::seq::SliceRandom;
+
+#[tokio::main]
+async fn main() -> Result<(), client::Error> {
+    simple_logger::SimpleLogger::new()
+        .with_level(log::LevelFilter::Info)
+        .env()
+        .init()
+        .unwrap();
+
+    let base_url = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "http://localhost:3000".to_string());
+    let client = Client::new(base_url);
+
+    let game = client.host().await?;
+    info!("hosted lobby {}", game.lobby_id);
+    let mut connection = client.connect(game.lobby_id, game.session).await?;
+
+    let mut state: Option<(Board, Color)> = None;
+    while let Some(msg) = connection.next().await {
+        match msg {
+            ServerMsg::OpponentJoined => {
+                // reconnect to get the initial position
+                connection = client.connect(game.lobby_id, game.session).await?;
+            }
+            ServerMsg::Connected(GameState::Ingame { fen, color }) => {
+                state = Some((fen.parse().unwrap(), color));
+            }
+            ServerMsg::PlayedMove(chess_move) => {
+                if let Some((board, _)) = &mut state {
+                    board.play(chess_move);
+                }
+            }
+            other => info!("received {other:?}"),
+        }
+        if let Some((board, color)) = &state {
+            if board.side_to_move() == *color {
+                let mut moves = Vec::new();
+                board.generate_moves(|piece_moves| {
+                    moves.extend(piece_moves);
+                    false
+                });
+                match moves.choose(&mut rand::thread_rng()) {
+                    Some(&chess_move) => connection.play_move(chess_move).await?,
+                    None => {
+                        info!("game over");
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,334 @@
+//! Native client for the game protocol, used for bots, load tests and integration tests.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), client::Error> {
+//! use futures::StreamExt;
+//!
+//! let client = client::Client::new("http://localhost:3000");
+//! let game = client.host().await?;
+//! let mut connection = client.connect(game.lobby_id, game.session).await?;
+//! while let Some(msg) = connection.next().await {
+//!     println!("received {msg:?}");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use common::{
+    http::{
+        BotSettings, HostRequest, HostResponse, JoinResponse, LobbyInfo, LobbySettings,
+        LoginRequest, RegisterRequest, SeekRequest, SeekResponse, UserInfo,
+    },
+    ws::{
+        encoding::Encoding,
+        message::{Limits, Version},
+        ClientMsg, ServerMsg,
+    },
+};
+use cozy_chess::Move;
+use futures::{
+    stream::{SplitSink, SplitStream},
+    SinkExt, Stream, StreamExt,
+};
+use log::{debug, warn};
+use reqwest::StatusCode;
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use thiserror::Error;
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    tungstenite::{self, Message as WsMessage},
+    MaybeTlsStream, WebSocketStream,
+};
+use uuid::Uuid;
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("http request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("unknown lobby")]
+    UnknownLobby,
+    #[error("game is already running")]
+    GameRunning,
+    #[error("wrong username or password")]
+    InvalidCredentials,
+    #[error("username is already taken")]
+    UsernameTaken,
+    #[error("unknown or expired seek")]
+    UnknownSeek,
+    #[error("connection closed unexpectedly")]
+    Closed,
+    #[error("unhandled status code {0}")]
+    Status(StatusCode),
+    #[error("websocket error: {0}")]
+    WebSocket(Box<tungstenite::Error>),
+    #[error("failed to encode message: {0}")]
+    Encode(#[from] io::Error),
+}
+
+impl From<tungstenite::Error> for Error {
+    fn from(value: tungstenite::Error) -> Self {
+        Self::WebSocket(Box::new(value))
+    }
+}
+
+/// Entry point for talking to a server.
+///
+/// Games are played as a guest unless the client logged in, the login is kept in a cookie.
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    encoding: Encoding,
+}
+
+impl Client {
+    /// Creates a client for the server at `base_url`, e.g. `http://localhost:3000`.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::builder()
+                .cookie_store(true)
+                .build()
+                .expect("client configuration is valid"),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            encoding: Encoding::Binary(Version::CURRENT),
+        }
+    }
+
+    /// Sets the encoding used by connections opened with [`Client::connect`].
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Creates an account and logs in.
+    pub async fn register(&self, request: &RegisterRequest) -> Result<UserInfo, Error> {
+        let response = self
+            .http
+            .post(format!("{}/api/account/register", self.base_url))
+            .json(request)
+            .send()
+            .await?;
+        match response.status() {
+            StatusCode::OK => Ok(response.json().await?),
+            StatusCode::CONFLICT => Err(Error::UsernameTaken),
+            other => Err(Error::Status(other)),
+        }
+    }
+
+    /// Logs in, games hosted or joined afterwards are played under the account's display name.
+    pub async fn login(&self, username: &str, password: &str) -> Result<UserInfo, Error> {
+        let response = self
+            .http
+            .post(format!("{}/api/account/login", self.base_url))
+            .json(&LoginRequest {
+                username: username.to_string(),
+                password: password.to_string(),
+            })
+            .send()
+            .await?;
+        match response.status() {
+            StatusCode::OK => Ok(response.json().await?),
+            StatusCode::UNAUTHORIZED => Err(Error::InvalidCredentials),
+            other => Err(Error::Status(other)),
+        }
+    }
+
+    pub async fn host(&self) -> Result<HostResponse, Error> {
+        self.host_with(LobbySettings::default()).await
+    }
+
+    /// Hosts a game, e.g. a public one listed by [`Self::lobbies`].
+    pub async fn host_with(&self, settings: LobbySettings) -> Result<HostResponse, Error> {
+        let response = self
+            .http
+            .post(format!("{}/api/host", self.base_url))
+            .json(&HostRequest {
+                settings,
+                ..HostRequest::default()
+            })
+            .send()
+            .await?;
+        match response.status() {
+            StatusCode::OK => Ok(response.json().await?),
+            other => Err(Error::Status(other)),
+        }
+    }
+
+    /// Hosts a game against the server's engine, which takes the second seat right away.
+    pub async fn host_bot(&self, bot: BotSettings) -> Result<HostResponse, Error> {
+        let response = self
+            .http
+            .post(format!("{}/api/host", self.base_url))
+            .json(&HostRequest {
+                bot: Some(bot),
+                ..HostRequest::default()
+            })
+            .send()
+            .await?;
+        match response.status() {
+            StatusCode::OK => Ok(response.json().await?),
+            other => Err(Error::Status(other)),
+        }
+    }
+
+    /// Lists public lobbies waiting for an opponent.
+    pub async fn lobbies(&self) -> Result<Vec<LobbyInfo>, Error> {
+        let response = self
+            .http
+            .get(format!("{}/api/lobbies", self.base_url))
+            .send()
+            .await?;
+        match response.status() {
+            StatusCode::OK => Ok(response.json().await?),
+            other => Err(Error::Status(other)),
+        }
+    }
+
+    pub async fn join(&self, id: Uuid) -> Result<JoinResponse, Error> {
+        let response = self
+            .http
+            .post(format!("{}/api/join/{id}", self.base_url))
+            .send()
+            .await?;
+        match response.status() {
+            StatusCode::OK => Ok(response.json().await?),
+            StatusCode::NOT_FOUND => Err(Error::UnknownLobby),
+            StatusCode::CONFLICT => Err(Error::GameRunning),
+            other => Err(Error::Status(other)),
+        }
+    }
+
+    /// Looks for an opponent, see [`Self::wait_for_match`].
+    pub async fn seek(&self, request: SeekRequest) -> Result<SeekResponse, Error> {
+        let response = self
+            .http
+            .post(format!("{}/api/seek", self.base_url))
+            .json(&request)
+            .send()
+            .await?;
+        match response.status() {
+            StatusCode::OK => Ok(response.json().await?),
+            other => Err(Error::Status(other)),
+        }
+    }
+
+    /// Waits until the seek gets paired and returns the seat in the new game.
+    pub async fn wait_for_match(&self, seek_id: Uuid) -> Result<JoinResponse, Error> {
+        let mut connection = self.open().await?;
+        connection.send(ClientMsg::WaitForMatch { seek_id }).await?;
+        while let Some(msg) = connection.next().await {
+            match msg {
+                ServerMsg::Matched { lobby_id, session } => {
+                    return Ok(JoinResponse { lobby_id, session })
+                }
+                ServerMsg::InvalidSeek => return Err(Error::UnknownSeek),
+                msg => debug!("received {msg:?} while waiting for a match, ignoring"),
+            }
+        }
+        Err(Error::Closed)
+    }
+
+    /// Opens a WebSocket connection and sends [`ClientMsg::Connect`].
+    pub async fn connect(&self, lobby_id: Uuid, session: Uuid) -> Result<Connection, Error> {
+        let mut connection = self.open().await?;
+        connection
+            .send(ClientMsg::Connect { lobby_id, session })
+            .await?;
+        Ok(connection)
+    }
+
+    async fn open(&self) -> Result<Connection, Error> {
+        let url = match self.encoding {
+            Encoding::Binary(version) => format!(
+                "{}/ws?encoding=binary&version={}",
+                self.ws_base_url(),
+                u8::from(version)
+            ),
+            Encoding::Json => format!("{}/ws?encoding=json", self.ws_base_url()),
+        };
+        let (socket, _) = tokio_tungstenite::connect_async(url).await?;
+        let (sink, stream) = socket.split();
+        Ok(Connection {
+            sink,
+            stream,
+            encoding: self.encoding,
+        })
+    }
+
+    fn ws_base_url(&self) -> String {
+        if let Some(rest) = self.base_url.strip_prefix("https://") {
+            format!("wss://{rest}")
+        } else if let Some(rest) = self.base_url.strip_prefix("http://") {
+            format!("ws://{rest}")
+        } else {
+            self.base_url.clone()
+        }
+    }
+}
+
+/// A connection to a lobby.
+///
+/// Received messages are available through its [`Stream`] implementation,
+/// which ends once the server closes the connection.
+pub struct Connection {
+    sink: SplitSink<Socket, WsMessage>,
+    stream: SplitStream<Socket>,
+    encoding: Encoding,
+}
+
+impl Connection {
+    pub async fn send(&mut self, msg: ClientMsg) -> Result<(), Error> {
+        let bytes = self.encoding.encode(&msg)?;
+        let msg = match self.encoding {
+            Encoding::Binary(_) => WsMessage::Binary(bytes),
+            Encoding::Json => {
+                WsMessage::Text(String::from_utf8(bytes).expect("serde_json produces valid UTF-8"))
+            }
+        };
+        self.sink.send(msg).await?;
+        Ok(())
+    }
+
+    pub async fn play_move(&mut self, chess_move: Move) -> Result<(), Error> {
+        self.send(ClientMsg::PlayMove(chess_move)).await
+    }
+
+    pub async fn close(mut self) -> Result<(), Error> {
+        self.sink.close().await?;
+        Ok(())
+    }
+}
+
+impl Stream for Connection {
+    type Item = ServerMsg;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            let msg = match self.stream.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(msg))) => msg,
+                Poll::Ready(Some(Err(err))) => {
+                    debug!("error receiving message: {err}");
+                    return Poll::Ready(None);
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            };
+            let bytes = match msg {
+                WsMessage::Binary(bytes) => bytes,
+                WsMessage::Text(text) => text.into_bytes(),
+                WsMessage::Close(_) => return Poll::Ready(None),
+                _ => continue,
+            };
+            match self.encoding.decode(&bytes, Limits::default()) {
+                Ok(msg) => return Poll::Ready(Some(msg)),
+                Err(err) => warn!("error deserializing message: {err}"),
+            }
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! Plays games against a server running in the same process.
+
+use client::{Client, Connection};
+use common::ws::{encoding::Encoding, message::Version, GameState, ServerMsg};
+use cozy_chess::{Board, Color};
+use futures::StreamExt;
+use std::{net::TcpListener, time::Duration};
+use tempfile::TempDir;
+
+/// Starts a server with an empty database, which is removed when the returned directory
+/// is dropped
+fn serve() -> (String, TempDir) {
+    let database = tempfile::tempdir().unwrap();
+    let app = backend::app(backend::Config {
+        engine: None,
+        engine_move_time: Duration::from_millis(100),
+        database: database.path().to_path_buf(),
+    })
+    .unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let server = axum::Server::from_tcp(listener)
+        .unwrap()
+        .serve(app.into_make_service());
+    tokio::spawn(server);
+    (base_url, database)
+}
+
+async fn next(connection: &mut Connection) -> ServerMsg {
+    tokio::time::timeout(Duration::from_secs(5), connection.next())
+        .await
+        .expect("server answers in time")
+        .expect("connection stays open")
+}
+
+/// Hosts a game with the host's encoding, joins it with the guest's and checks that a move
+/// reaches both connections
+async fn play_first_move(host: Encoding, guest: Encoding) {
+    let (base_url, _database) = serve();
+    let host = Client::new(&base_url).with_encoding(host);
+    let guest = Client::new(&base_url).with_encoding(guest);
+
+    let hosted = host.host().await.unwrap();
+    let mut host_connection = host.connect(hosted.lobby_id, hosted.session).await.unwrap();
+    assert_eq!(
+        next(&mut host_connection).await,
+        ServerMsg::Connected(GameState::WaitingForOpponent)
+    );
+
+    let joined = guest.join(hosted.lobby_id).await.unwrap();
+    assert_eq!(joined.lobby_id, hosted.lobby_id);
+    assert_eq!(next(&mut host_connection).await, ServerMsg::OpponentJoined);
+
+    let mut connections = Vec::new();
+    for (client, session) in [(&host, hosted.session), (&guest, joined.session)] {
+        let mut connection = client.connect(hosted.lobby_id, session).await.unwrap();
+        let ServerMsg::Connected(GameState::Ingame { fen, color }) = next(&mut connection).await
+        else {
+            panic!("expected to connect to the running game");
+        };
+        assert_eq!(fen, Board::default().to_string());
+        connections.push((color, connection));
+    }
+    assert_ne!(connections[0].0, connections[1].0);
+
+    let chess_move = "e2e4".parse().unwrap();
+    let (_, white) = connections
+        .iter_mut()
+        .find(|(color, _)| *color == Color::White)
+        .unwrap();
+    white.play_move(chess_move).await.unwrap();
+    for (_, connection) in &mut connections {
+        // the players and the state of the game are sent after connecting
+        let played = loop {
+            if let ServerMsg::PlayedMove(played) = next(connection).await {
+                break played;
+            }
+        };
+        assert_eq!(played, chess_move);
+    }
+}
+
+#[tokio::test]
+async fn binary_connections() {
+    play_first_move(
+        Encoding::Binary(Version::CURRENT),
+        Encoding::Binary(Version::CURRENT),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn json_connections() {
+    play_first_move(Encoding::Json, Encoding::Json).await;
+}
+
+#[tokio::test]
+async fn mixed_encodings_and_versions() {
+    play_first_move(Encoding::Binary(Version::V1), Encoding::Json).await;
+    play_first_move(Encoding::Json, Encoding::Binary(Version::V2)).await;
+}
+
+#[tokio::test]
+async fn joining_unknown_lobbies_fails() {
+    let (base_url, _database) = serve();
+    let client = Client::new(base_url);
+    assert!(matches!(
+        client.join(uuid::Uuid::new_v4()).await,
+        Err(client::Error::UnknownLobby)
+    ));
+}
@@ -0,0 +1,90 @@
+use common::ws::{
+    message::{Limits, Message, Version},
+    ClientMsg, GameState, ServerMsg,
+};
+use cozy_chess::{Board, Color, Move, Piece, Square};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use uuid::Uuid;
+
+fn messages() -> Vec<(&'static str, ServerMsg)> {
+    vec![
+        (
+            "played_move",
+            ServerMsg::PlayedMove(Move {
+                from: Square::E7,
+                to: Square::E8,
+                promotion: Some(Piece::Queen),
+            }),
+        ),
+        (
+            "connected",
+            ServerMsg::Connected(GameState::Ingame {
+                fen: Board::default().to_string(),
+                color: Color::Black,
+            }),
+        ),
+    ]
+}
+
+fn encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode");
+    for (name, msg) in messages() {
+        for version in [Version::V1, Version::V2] {
+            group.bench_with_input(
+                BenchmarkId::new(name, format!("{version:?}")),
+                &msg,
+                |b, msg| {
+                    let mut buf = Vec::new();
+                    b.iter(|| {
+                        buf.clear();
+                        black_box(msg).encode_versioned(&mut buf, version).unwrap();
+                    })
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    for (name, msg) in messages() {
+        for version in [Version::V1, Version::V2] {
+            let mut buf = Vec::new();
+            msg.encode_versioned(&mut buf, version).unwrap();
+            println!("{name} {version:?}: {} bytes", buf.len());
+            group.bench_with_input(
+                BenchmarkId::new(name, format!("{version:?}")),
+                &buf,
+                |b, buf| {
+                    b.iter(|| {
+                        ServerMsg::decode_versioned(black_box(&buf[..]), version, Limits::default())
+                            .unwrap()
+                    })
+                },
+            );
+        }
+    }
+    let connect = ClientMsg::Connect {
+        lobby_id: Uuid::from_u128(1),
+        session: Uuid::from_u128(2),
+    };
+    for version in [Version::V1, Version::V2] {
+        let mut buf = Vec::new();
+        connect.encode_versioned(&mut buf, version).unwrap();
+        group.bench_with_input(
+            BenchmarkId::new("connect", format!("{version:?}")),
+            &buf,
+            |b, buf| {
+                b.iter(|| {
+                    ClientMsg::decode_versioned(black_box(&buf[..]), version, Limits::default())
+                        .unwrap()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, encode, decode);
+criterion_main!(benches);
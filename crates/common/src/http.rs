@@ -1,4 +1,8 @@
+use crate::ws::message;
+use proc_macros::Message;
 use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+use thiserror::Error;
 use uuid::Uuid;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
@@ -12,3 +16,281 @@ pub struct JoinResponse {
     pub lobby_id: Uuid,
     pub session: Uuid,
 }
+
+/// Optional body of `/api/host`, hosting without a body waits for a human opponent
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub struct HostRequest {
+    /// Play against the server's engine instead of waiting for an opponent
+    #[serde(default)]
+    pub bot: Option<BotSettings>,
+    #[serde(default)]
+    pub settings: LobbySettings,
+    /// Position the games start from, the initial position if not specified. Games from other
+    /// positions can't be rated.
+    #[serde(default)]
+    pub fen: Option<String>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default, Deserialize, Serialize, Message)]
+#[serde(rename_all = "snake_case")]
+pub enum Variant {
+    /// Only standard chess is supported so far
+    #[default]
+    Standard,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub struct LobbySettings {
+    /// List the lobby in `/api/lobbies` while it waits for an opponent
+    #[serde(default)]
+    pub public: bool,
+    #[serde(default)]
+    pub time_control: Option<TimeControl>,
+    #[serde(default)]
+    pub variant: Variant,
+    /// Update the ratings of both players once the game is over, requires a time control and
+    /// both players to be logged in
+    #[serde(default)]
+    pub rated: bool,
+    #[serde(default)]
+    pub takebacks: Takebacks,
+}
+
+/// Which games of a lobby allow taking back moves
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Takebacks {
+    Always,
+    /// Allow takebacks unless the game is rated
+    #[default]
+    Casual,
+    Never,
+}
+
+impl Takebacks {
+    pub fn allowed(&self, rated: bool) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Casual => !rated,
+            Self::Never => false,
+        }
+    }
+}
+
+/// Public lobby waiting for an opponent, as listed by `/api/lobbies`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LobbyInfo {
+    pub id: Uuid,
+    pub time_control: Option<TimeControl>,
+    pub rated: bool,
+    pub variant: Variant,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub struct BotSettings {
+    /// Thinking time per move of an external engine in milliseconds, the server default is
+    /// used if not set
+    #[serde(default)]
+    pub move_time: Option<u64>,
+    /// Strength of the built-in engine, which is used instead of an external one if set
+    #[serde(default)]
+    pub level: Option<u8>,
+}
+
+/// Body of `/api/bot/account`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct BotAccountRequest {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct BotAccountResponse {
+    /// Sent as `Authorization: Bearer <token>` to the other bot endpoints
+    pub token: String,
+}
+
+/// Response of the bot challenge endpoints
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ChallengeResponse {
+    pub game_id: Uuid,
+}
+
+/// Initial time on the clock and time added after every move, both in seconds
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct TimeControl {
+    pub initial: u32,
+    pub increment: u32,
+}
+
+impl TimeControl {
+    /// Longest initial time accepted by the server
+    pub const MAX_INITIAL: u32 = 3 * 60 * 60;
+    /// Largest increment accepted by the server
+    pub const MAX_INCREMENT: u32 = 3 * 60;
+
+    pub fn is_valid(&self) -> bool {
+        (self.initial > 0 || self.increment > 0)
+            && self.initial <= Self::MAX_INITIAL
+            && self.increment <= Self::MAX_INCREMENT
+    }
+
+    /// Category of the time control, based on the estimated duration of a 40 move game
+    pub fn category(&self) -> RatingCategory {
+        // saturate for time controls that never went through `is_valid`
+        match self
+            .increment
+            .saturating_mul(40)
+            .saturating_add(self.initial)
+        {
+            ..=179 => RatingCategory::Bullet,
+            180..=479 => RatingCategory::Blitz,
+            480..=1499 => RatingCategory::Rapid,
+            _ => RatingCategory::Classical,
+        }
+    }
+}
+
+/// Players have a separate rating for each category of time controls
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RatingCategory {
+    Bullet,
+    Blitz,
+    Rapid,
+    Classical,
+}
+
+impl RatingCategory {
+    pub const ALL: [Self; 4] = [Self::Bullet, Self::Blitz, Self::Rapid, Self::Classical];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Bullet => "bullet",
+            Self::Blitz => "blitz",
+            Self::Rapid => "rapid",
+            Self::Classical => "classical",
+        }
+    }
+}
+
+impl fmt::Display for RatingCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.initial.is_multiple_of(60) {
+            write!(f, "{}+{}", self.initial / 60, self.increment)
+        } else {
+            write!(f, "{}s+{}", self.initial, self.increment)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("invalid time control '{0}', expected minutes and increment like '5+3'")]
+pub struct InvalidTimeControl(String);
+
+impl FromStr for TimeControl {
+    type Err = InvalidTimeControl;
+
+    /// Parses the format written by [`Display`](fmt::Display), e.g. `5+3` or `30s+0`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidTimeControl(s.to_string());
+        let (initial, increment) = s.split_once('+').ok_or_else(invalid)?;
+        let initial = match initial.strip_suffix('s') {
+            Some(seconds) => seconds.parse().map_err(|_| invalid())?,
+            None => initial
+                .parse::<u32>()
+                .map_err(|_| invalid())?
+                .checked_mul(60)
+                .ok_or_else(invalid)?,
+        };
+        let increment = increment.parse().map_err(|_| invalid())?;
+        Ok(Self { initial, increment })
+    }
+}
+
+/// Inclusive range of opponent ratings
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RatingRange {
+    pub min: u32,
+    pub max: u32,
+}
+
+impl RatingRange {
+    pub fn contains(&self, rating: u32) -> bool {
+        (self.min..=self.max).contains(&rating)
+    }
+}
+
+/// Body of `/api/seek`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SeekRequest {
+    pub time_control: TimeControl,
+    #[serde(default)]
+    pub rating_range: Option<RatingRange>,
+    /// Only pair with other rated seeks, requires being logged in
+    #[serde(default)]
+    pub rated: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SeekResponse {
+    /// Sent in [`crate::ws::ClientMsg::WaitForMatch`] to wait for an opponent
+    pub seek_id: Uuid,
+}
+
+/// Body of `/api/account/register`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RegisterRequest {
+    pub username: String,
+    pub password: String,
+    /// Name shown to opponents, defaults to the username
+    #[serde(default)]
+    pub display_name: Option<String>,
+}
+
+/// Body of `/api/account/login`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LoginRequest {
+    pub username: String,
+    pub password: String,
+}
+
+/// Body of `/api/account/display_name`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DisplayNameRequest {
+    pub display_name: String,
+}
+
+/// The logged in user, returned by the account endpoints
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct UserInfo {
+    pub username: String,
+    pub display_name: String,
+}
+
+/// A game as returned by `/api/game/:id`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct GameRecord {
+    pub white: String,
+    pub black: String,
+    /// Position the game started from
+    pub fen: String,
+    /// Moves played since that position in UCI notation
+    pub moves: Vec<String>,
+}
+
+/// Entry of `/api/leaderboard/:category`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LeaderboardEntry {
+    pub username: String,
+    pub display_name: String,
+    pub rating: u32,
+    /// Rating deviation, the lower the more certain the rating is
+    pub deviation: u32,
+    pub games: u32,
+}
@@ -1,2 +1,4 @@
 pub mod http;
+pub mod notation;
+pub mod pgn;
 pub mod ws;
@@ -0,0 +1,411 @@
+//! Conversion between [`Move`]s and human readable notations.
+//!
+//! cozy-chess represents castling as the king capturing its own rook, so these helpers
+//! also translate between that and the usual `e1g1` UCI notation.
+
+use cozy_chess::{Board, File, Move, Piece, Rank, Square};
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum NotationError {
+    #[error("'{0}' is not a valid move")]
+    Invalid(String),
+    #[error("'{0}' is not a legal move")]
+    Illegal(String),
+    #[error("'{0}' is ambiguous")]
+    Ambiguous(String),
+}
+
+fn legal_moves(board: &Board) -> Vec<Move> {
+    let mut moves = Vec::new();
+    board.generate_moves(|piece_moves| {
+        moves.extend(piece_moves);
+        false
+    });
+    moves
+}
+
+fn is_castle(board: &Board, chess_move: Move) -> bool {
+    board.piece_on(chess_move.from) == Some(Piece::King)
+        && board.color_on(chess_move.to) == board.color_on(chess_move.from)
+}
+
+/// Parses a move in UCI notation, accepting both `e1g1` and `e1h1` for castling.
+pub fn parse_uci(board: &Board, str: &str) -> Result<Move, NotationError> {
+    let mut chess_move: Move = str
+        .parse()
+        .map_err(|_| NotationError::Invalid(str.to_string()))?;
+    if board.piece_on(chess_move.from) == Some(Piece::King)
+        && board.color_on(chess_move.from) == Some(board.side_to_move())
+        && chess_move.from.rank() == chess_move.to.rank()
+    {
+        let rights = board.castle_rights(board.side_to_move());
+        let rook_file = match chess_move.to.file() as i8 - chess_move.from.file() as i8 {
+            2 => rights.short,
+            -2 => rights.long,
+            _ => None,
+        };
+        if let Some(file) = rook_file {
+            chess_move.to = Square::new(file, chess_move.to.rank());
+        }
+    }
+    if board.is_legal(chess_move) {
+        Ok(chess_move)
+    } else {
+        Err(NotationError::Illegal(str.to_string()))
+    }
+}
+
+/// Formats a move in UCI notation, writing castling as the king moving two squares.
+pub fn to_uci(board: &Board, chess_move: Move) -> String {
+    if is_castle(board, chess_move) {
+        let file = if chess_move.to.file() > chess_move.from.file() {
+            File::G
+        } else {
+            File::C
+        };
+        let to = Square::new(file, chess_move.to.rank());
+        format!("{}{to}", chess_move.from)
+    } else {
+        chess_move.to_string()
+    }
+}
+
+/// Formats a legal move in standard algebraic notation, e.g. `Nbd7`, `exd5`, `O-O` or `e8=Q#`.
+pub fn to_san(board: &Board, chess_move: Move) -> String {
+    let mut san = if is_castle(board, chess_move) {
+        if chess_move.to.file() > chess_move.from.file() {
+            "O-O".to_string()
+        } else {
+            "O-O-O".to_string()
+        }
+    } else {
+        let piece = board.piece_on(chess_move.from).unwrap_or(Piece::Pawn);
+        let capture = board.color_on(chess_move.to).is_some()
+            || (piece == Piece::Pawn && chess_move.from.file() != chess_move.to.file());
+        let mut san = String::new();
+        if piece == Piece::Pawn {
+            if capture {
+                san.push_str(&chess_move.from.file().to_string());
+            }
+        } else {
+            san.push_str(&piece.to_string().to_uppercase());
+            let others: Vec<_> = legal_moves(board)
+                .into_iter()
+                .filter(|other| {
+                    other.to == chess_move.to
+                        && other.from != chess_move.from
+                        && board.piece_on(other.from) == Some(piece)
+                })
+                .collect();
+            if !others.is_empty() {
+                let same_file = others
+                    .iter()
+                    .any(|other| other.from.file() == chess_move.from.file());
+                let same_rank = others
+                    .iter()
+                    .any(|other| other.from.rank() == chess_move.from.rank());
+                if !same_file {
+                    san.push_str(&chess_move.from.file().to_string());
+                } else if !same_rank {
+                    san.push_str(&chess_move.from.rank().to_string());
+                } else {
+                    san.push_str(&chess_move.from.to_string());
+                }
+            }
+        }
+        if capture {
+            san.push('x');
+        }
+        san.push_str(&chess_move.to.to_string());
+        if let Some(promotion) = chess_move.promotion {
+            san.push('=');
+            san.push_str(&promotion.to_string().to_uppercase());
+        }
+        san
+    };
+
+    let mut after = board.clone();
+    after.play_unchecked(chess_move);
+    if !after.checkers().is_empty() {
+        san.push(if after.generate_moves(|_| true) {
+            '+'
+        } else {
+            '#'
+        });
+    }
+    san
+}
+
+/// Parses a move in standard algebraic notation.
+///
+/// Captures and promotions don't need to be marked and superfluous disambiguation is
+/// allowed, so `exd5`, `ed5`, `e8Q` and `Ngf3` are accepted.
+pub fn parse_san(board: &Board, str: &str) -> Result<Move, NotationError> {
+    let invalid = || NotationError::Invalid(str.to_string());
+    let normalized: String = str
+        .trim()
+        .trim_end_matches(['+', '#', '!', '?'])
+        .replace('0', "O")
+        .chars()
+        .filter(|&c| c != 'x' && c != '=')
+        .collect();
+
+    let candidates = legal_moves(board).into_iter();
+    let mut matches: Vec<Move> = match normalized.as_str() {
+        "O-O" | "O-O-O" => candidates
+            .filter(|&chess_move| {
+                is_castle(board, chess_move)
+                    && (chess_move.to.file() > chess_move.from.file()) == (normalized == "O-O")
+            })
+            .collect(),
+        _ => {
+            let mut chars: Vec<char> = normalized.chars().collect();
+            let piece = match chars.first() {
+                Some(c) if c.is_ascii_uppercase() => {
+                    let piece = c
+                        .to_string()
+                        .to_lowercase()
+                        .parse()
+                        .map_err(|_| invalid())?;
+                    chars.remove(0);
+                    piece
+                }
+                _ => Piece::Pawn,
+            };
+            let promotion = match chars.last() {
+                Some(c) if c.is_ascii_alphabetic() => {
+                    let piece = c
+                        .to_string()
+                        .to_lowercase()
+                        .parse()
+                        .map_err(|_| invalid())?;
+                    chars.pop();
+                    Some(piece)
+                }
+                _ => None,
+            };
+            if chars.len() < 2 {
+                return Err(invalid());
+            }
+            let to: Square = chars
+                .split_off(chars.len() - 2)
+                .into_iter()
+                .collect::<String>()
+                .parse()
+                .map_err(|_| invalid())?;
+            let (mut from_file, mut from_rank) = (None, None);
+            for c in chars {
+                if let Ok(file) = c.to_string().parse::<File>() {
+                    from_file = Some(file);
+                } else if let Ok(rank) = c.to_string().parse::<Rank>() {
+                    from_rank = Some(rank);
+                } else {
+                    return Err(invalid());
+                }
+            }
+            candidates
+                .filter(|chess_move| {
+                    chess_move.to == to
+                        && chess_move.promotion == promotion
+                        && board.piece_on(chess_move.from) == Some(piece)
+                        && !is_castle(board, *chess_move)
+                        && from_file.is_none_or(|file| chess_move.from.file() == file)
+                        && from_rank.is_none_or(|rank| chess_move.from.rank() == rank)
+                })
+                .collect()
+        }
+    };
+    match (matches.pop(), matches.is_empty()) {
+        (Some(chess_move), true) => Ok(chess_move),
+        (Some(_), false) => Err(NotationError::Ambiguous(str.to_string())),
+        (None, _) => Err(NotationError::Illegal(str.to_string())),
+    }
+}
+
+/// Parses a move in either UCI or standard algebraic notation.
+pub fn parse_move(board: &Board, str: &str) -> Result<Move, NotationError> {
+    let str = str.trim();
+    parse_uci(board, str).or_else(|uci_err| match parse_san(board, str) {
+        Ok(chess_move) => Ok(chess_move),
+        Err(NotationError::Illegal(_)) if matches!(uci_err, NotationError::Illegal(_)) => {
+            Err(uci_err)
+        }
+        Err(err) => Err(err),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(fen: &str) -> Board {
+        fen.parse().unwrap()
+    }
+
+    fn uci(board: &Board, uci: &str) -> Move {
+        parse_uci(board, uci).unwrap()
+    }
+
+    /// Both sides can castle both ways
+    const CASTLING: &str = "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1";
+
+    #[test]
+    fn uci_castling_is_translated() {
+        let board = board(CASTLING);
+        let short = Move {
+            from: Square::E1,
+            to: Square::H1,
+            promotion: None,
+        };
+        let long = Move {
+            from: Square::E1,
+            to: Square::A1,
+            promotion: None,
+        };
+        assert_eq!(parse_uci(&board, "e1g1"), Ok(short));
+        assert_eq!(parse_uci(&board, "e1h1"), Ok(short));
+        assert_eq!(parse_uci(&board, "e1c1"), Ok(long));
+        assert_eq!(parse_uci(&board, "e1a1"), Ok(long));
+        assert_eq!(to_uci(&board, short), "e1g1");
+        assert_eq!(to_uci(&board, long), "e1c1");
+    }
+
+    #[test]
+    fn uci_without_castle_rights() {
+        let board = board("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w - - 0 1");
+        assert_eq!(
+            parse_uci(&board, "e1g1"),
+            Err(NotationError::Illegal("e1g1".to_string()))
+        );
+    }
+
+    #[test]
+    fn uci_errors() {
+        let board = Board::default();
+        assert_eq!(
+            parse_uci(&board, "e2e5"),
+            Err(NotationError::Illegal("e2e5".to_string()))
+        );
+        assert_eq!(
+            parse_uci(&board, "e2"),
+            Err(NotationError::Invalid("e2".to_string()))
+        );
+    }
+
+    #[test]
+    fn uci_promotion() {
+        let board = board("8/4P3/8/8/8/k7/8/K7 w - - 0 1");
+        let chess_move = uci(&board, "e7e8n");
+        assert_eq!(chess_move.promotion, Some(Piece::Knight));
+        assert_eq!(to_uci(&board, chess_move), "e7e8n");
+        assert!(parse_uci(&board, "e7e8").is_err());
+    }
+
+    #[test]
+    fn san_castling() {
+        let board = board(CASTLING);
+        assert_eq!(to_san(&board, uci(&board, "e1g1")), "O-O");
+        assert_eq!(to_san(&board, uci(&board, "e1c1")), "O-O-O");
+        assert_eq!(parse_san(&board, "O-O"), Ok(uci(&board, "e1g1")));
+        assert_eq!(parse_san(&board, "0-0-0"), Ok(uci(&board, "e1c1")));
+        // the king capturing its own rook isn't a king move to that square
+        assert!(parse_san(&board, "Kh1").is_err());
+    }
+
+    #[test]
+    fn san_pawn_moves_and_captures() {
+        let board = board("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2");
+        assert_eq!(to_san(&board, uci(&board, "e4e5")), "e5");
+        assert_eq!(to_san(&board, uci(&board, "e4d5")), "exd5");
+        for san in ["exd5", "ed5", "exd5!?"] {
+            assert_eq!(parse_san(&board, san), Ok(uci(&board, "e4d5")));
+        }
+    }
+
+    #[test]
+    fn san_en_passant() {
+        let board = board("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3");
+        let chess_move = uci(&board, "e5f6");
+        assert_eq!(to_san(&board, chess_move), "exf6");
+        assert_eq!(parse_san(&board, "exf6"), Ok(chess_move));
+    }
+
+    #[test]
+    fn san_promotion() {
+        let board = board("3r4/4P3/8/8/8/k7/8/K7 w - - 0 1");
+        assert_eq!(to_san(&board, uci(&board, "e7e8q")), "e8=Q");
+        assert_eq!(to_san(&board, uci(&board, "e7d8r")), "exd8=R");
+        assert_eq!(parse_san(&board, "e8=Q"), Ok(uci(&board, "e7e8q")));
+        assert_eq!(parse_san(&board, "e8N"), Ok(uci(&board, "e7e8n")));
+        assert_eq!(parse_san(&board, "exd8=B"), Ok(uci(&board, "e7d8b")));
+        assert_eq!(
+            parse_san(&board, "e8"),
+            Err(NotationError::Illegal("e8".to_string()))
+        );
+    }
+
+    #[test]
+    fn san_disambiguation() {
+        // knights on b1 and f3 both reach d2, rooks on a1 and a5 both reach a3,
+        // and queens on e4, h4 and h7 all reach e7
+        let board = board("2k5/7Q/8/R7/4Q2Q/5N2/8/RN2K3 w - - 0 1");
+        assert_eq!(to_san(&board, uci(&board, "b1d2")), "Nbd2");
+        assert_eq!(to_san(&board, uci(&board, "a1a3")), "R1a3");
+        assert_eq!(to_san(&board, uci(&board, "h4e7")), "Qh4e7");
+        assert_eq!(parse_san(&board, "Nfd2"), Ok(uci(&board, "f3d2")));
+        assert_eq!(parse_san(&board, "R5a3"), Ok(uci(&board, "a5a3")));
+        assert_eq!(parse_san(&board, "Qh4e7"), Ok(uci(&board, "h4e7")));
+        // superfluous disambiguation is fine
+        assert_eq!(parse_san(&board, "Nf3g5"), Ok(uci(&board, "f3g5")));
+        assert_eq!(
+            parse_san(&board, "Nd2"),
+            Err(NotationError::Ambiguous("Nd2".to_string()))
+        );
+        assert_eq!(
+            parse_san(&board, "Qe7"),
+            Err(NotationError::Ambiguous("Qe7".to_string()))
+        );
+    }
+
+    #[test]
+    fn san_check_and_mate_suffixes() {
+        let mate = board("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1");
+        assert_eq!(to_san(&mate, uci(&mate, "a1a8")), "Ra8#");
+        assert_eq!(parse_san(&mate, "Ra8#"), Ok(uci(&mate, "a1a8")));
+        assert_eq!(parse_san(&mate, "Ra8"), Ok(uci(&mate, "a1a8")));
+
+        let check = board("6k1/8/8/8/8/8/5PPP/R5K1 w - - 0 1");
+        assert_eq!(to_san(&check, uci(&check, "a1a8")), "Ra8+");
+        assert_eq!(parse_san(&check, "Ra8+"), Ok(uci(&check, "a1a8")));
+    }
+
+    #[test]
+    fn san_errors() {
+        let board = Board::default();
+        assert_eq!(
+            parse_san(&board, "Ke2"),
+            Err(NotationError::Illegal("Ke2".to_string()))
+        );
+        assert_eq!(
+            parse_san(&board, "Zf3"),
+            Err(NotationError::Invalid("Zf3".to_string()))
+        );
+        assert_eq!(
+            parse_san(&board, "N"),
+            Err(NotationError::Invalid("N".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_move_accepts_both_notations() {
+        let board = Board::default();
+        let chess_move = uci(&board, "g1f3");
+        assert_eq!(parse_move(&board, " g1f3 "), Ok(chess_move));
+        assert_eq!(parse_move(&board, "Nf3"), Ok(chess_move));
+        assert_eq!(
+            parse_move(&board, "e2e5"),
+            Err(NotationError::Illegal("e2e5".to_string()))
+        );
+    }
+}
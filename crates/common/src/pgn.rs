@@ -0,0 +1,189 @@
+//! Export of games in the Portable Game Notation.
+
+use crate::{notation, ws::GameResult};
+use cozy_chess::{Board, Color, Move};
+use std::fmt::Write;
+
+/// Movetext lines are wrapped before reaching this length
+const MAX_LINE_LEN: usize = 80;
+
+/// Result as written in the `Result` tag and at the end of the movetext, `None` for ongoing games
+pub fn result(result: Option<GameResult>) -> &'static str {
+    match result {
+        Some(GameResult::WhiteWins) => "1-0",
+        Some(GameResult::BlackWins) => "0-1",
+        Some(GameResult::Draw) => "1/2-1/2",
+        None => "*",
+    }
+}
+
+/// Writes a game that started from `start`. `tags` should contain the seven tag roster, the
+/// `Result` tag is expected to match `result` and games from other than the initial position
+/// need the `SetUp` and `FEN` tags.
+pub fn write(
+    tags: &[(&str, &str)],
+    start: &Board,
+    moves: &[Move],
+    result: Option<GameResult>,
+) -> String {
+    let mut pgn = String::new();
+    for (name, value) in tags {
+        let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+        _ = writeln!(pgn, "[{name} \"{value}\"]");
+    }
+    pgn.push('\n');
+
+    let mut board = start.clone();
+    let mut tokens = Vec::with_capacity(moves.len() * 3 / 2 + 1);
+    for (ply, &chess_move) in moves.iter().enumerate() {
+        let number = board.fullmove_number();
+        if board.side_to_move() == Color::White {
+            tokens.push(format!("{number}."));
+        } else if ply == 0 {
+            tokens.push(format!("{number}..."));
+        }
+        tokens.push(notation::to_san(&board, chess_move));
+        board.play_unchecked(chess_move);
+    }
+    tokens.push(self::result(result).to_string());
+
+    let mut line_len = 0;
+    for token in tokens {
+        if line_len > 0 && line_len + 1 + token.len() > MAX_LINE_LEN {
+            pgn.push('\n');
+            line_len = 0;
+        } else if line_len > 0 {
+            pgn.push(' ');
+            line_len += 1;
+        }
+        line_len += token.len();
+        pgn.push_str(&token);
+    }
+    pgn.push('\n');
+    pgn
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(fen: &str) -> Board {
+        fen.parse().unwrap()
+    }
+
+    /// Plays moves given in UCI notation from `start`
+    fn moves(start: &Board, moves: &[&str]) -> Vec<Move> {
+        let mut board = start.clone();
+        moves
+            .iter()
+            .map(|uci| {
+                let chess_move = notation::parse_uci(&board, uci).unwrap();
+                board.play(chess_move);
+                chess_move
+            })
+            .collect()
+    }
+
+    /// Lines after the empty line that ends the tag pairs
+    fn movetext(pgn: &str) -> Vec<&str> {
+        pgn.lines()
+            .skip_while(|line| !line.is_empty())
+            .skip(1)
+            .collect()
+    }
+
+    #[test]
+    fn results() {
+        assert_eq!(result(Some(GameResult::WhiteWins)), "1-0");
+        assert_eq!(result(Some(GameResult::BlackWins)), "0-1");
+        assert_eq!(result(Some(GameResult::Draw)), "1/2-1/2");
+        assert_eq!(result(None), "*");
+    }
+
+    #[test]
+    fn move_numbers_and_suffixes() {
+        let start = Board::default();
+        let fools_mate = moves(&start, &["f2f3", "e7e5", "g2g4", "d8h4"]);
+        let pgn = write(
+            &[("Result", "0-1")],
+            &start,
+            &fools_mate,
+            Some(GameResult::BlackWins),
+        );
+        assert_eq!(pgn, "[Result \"0-1\"]\n\n1. f3 e5 2. g4 Qh4# 0-1\n");
+
+        let pgn = write(&[], &start, &fools_mate[..3], None);
+        assert_eq!(movetext(&pgn), ["1. f3 e5 2. g4 *"]);
+
+        let start = board("6k1/8/8/8/8/8/5PPP/R5K1 w - - 0 1");
+        let check = moves(&start, &["a1a8", "g8h7"]);
+        let pgn = write(&[], &start, &check, None);
+        assert_eq!(movetext(&pgn), ["1. Ra8+ Kh7 *"]);
+    }
+
+    #[test]
+    fn games_starting_with_black_to_move() {
+        let fen = "4k3/8/8/8/8/8/4P3/4K3 b - - 0 12";
+        let start = board(fen);
+        let played = moves(&start, &["e8d7", "e2e4", "d7e6"]);
+        let pgn = write(
+            &[("Result", "*"), ("SetUp", "1"), ("FEN", fen)],
+            &start,
+            &played,
+            None,
+        );
+        assert_eq!(
+            pgn,
+            format!("[Result \"*\"]\n[SetUp \"1\"]\n[FEN \"{fen}\"]\n\n12... Kd7 13. e4 Ke6 *\n")
+        );
+    }
+
+    #[test]
+    fn games_lost_on_time() {
+        let start = Board::default();
+        let played = moves(&start, &["e2e4", "e7e5"]);
+        let pgn = write(
+            &[("Result", "1-0"), ("Termination", "time forfeit")],
+            &start,
+            &played,
+            Some(GameResult::WhiteWins),
+        );
+        assert_eq!(
+            pgn,
+            "[Result \"1-0\"]\n[Termination \"time forfeit\"]\n\n1. e4 e5 1-0\n"
+        );
+    }
+
+    #[test]
+    fn tag_values_are_escaped() {
+        let pgn = write(
+            &[("White", r#"The "King" \o/"#)],
+            &Board::default(),
+            &[],
+            None,
+        );
+        assert_eq!(pgn, "[White \"The \\\"King\\\" \\\\o/\"]\n\n*\n");
+    }
+
+    #[test]
+    fn long_movetext_is_wrapped() {
+        let start = Board::default();
+        let knights = ["g1f3", "g8f6", "f3g1", "f6g8"].repeat(20);
+        let played = moves(&start, &knights);
+        let pgn = write(&[], &start, &played, Some(GameResult::Draw));
+        let lines = movetext(&pgn);
+
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|line| line.len() <= MAX_LINE_LEN));
+        // lines are filled as far as the next token allows
+        for pair in lines.windows(2) {
+            let next = pair[1].split(' ').next().unwrap();
+            assert!(pair[0].len() + 1 + next.len() > MAX_LINE_LEN);
+        }
+
+        let tokens: Vec<_> = lines.join(" ").split(' ').map(str::to_string).collect();
+        assert_eq!(tokens[..6], ["1.", "Nf3", "Nf6", "2.", "Ng1", "Ng8"]);
+        assert_eq!(tokens[tokens.len() - 4..], ["40.", "Ng1", "Ng8", "1/2-1/2"]);
+        assert_eq!(tokens.len(), 40 * 3 + 1);
+    }
+}
@@ -1,27 +1,137 @@
+use crate::http::{TimeControl, Variant};
 use cozy_chess::{Color, Move};
 use proc_macros::Message;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use uuid::Uuid;
 
+pub mod encoding;
+pub mod json;
 pub mod message;
 
-#[derive(Debug, Clone, PartialEq, Message)]
+/// In JSON, messages are objects with a snake case `type` and, for variants that carry
+/// data, a `data` field, e.g. `{"type":"played_move","data":"e2e4"}`.
+/// Moves are UCI strings (castling is written as king takes rook, e.g. `e1h1`)
+/// and colors are `"white"` or `"black"`.
+#[derive(Debug, Clone, PartialEq, Message, Deserialize, Serialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
 pub enum GameState {
     WaitingForOpponent,
-    Ingame { fen: String, color: Color },
+    Ingame {
+        fen: String,
+        #[serde(with = "json::color")]
+        color: Color,
+    },
 }
 
-#[derive(Debug, Clone, PartialEq, Message)]
+#[derive(Debug, Clone, PartialEq, Message, Deserialize, Serialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
 pub enum ServerMsg {
     ConnectRequired,
     Connected(GameState),
     InvalidSession,
     InvalidLobby,
     OpponentJoined,
+    #[serde(with = "json::uci_move")]
     PlayedMove(Move),
     InvalidMove,
+    /// A seek was paired, the game can be joined with [`ClientMsg::Connect`]
+    Matched {
+        lobby_id: Uuid,
+        session: Uuid,
+    },
+    InvalidSeek,
+    /// A public lobby is waiting for an opponent, sent for every open lobby after
+    /// [`ClientMsg::WatchLobbies`] and whenever a new one is created
+    LobbyOpened {
+        id: Uuid,
+        time_control: Option<TimeControl>,
+        variant: Variant,
+        rated: bool,
+    },
+    /// A public lobby was filled or expired
+    LobbyClosed {
+        id: Uuid,
+    },
+    /// Display names of both players, sent after [`ServerMsg::Connected`] once the game started
+    Players {
+        white: String,
+        black: String,
+    },
+    /// The game ended, rated games include the new ratings of white and black
+    GameOver {
+        result: GameResult,
+        reason: GameOverReason,
+        ratings: Option<[RatingChange; 2]>,
+    },
+    /// The player of this color wants to take back their last move, the request is dropped
+    /// when the next move is played
+    #[serde(with = "json::color")]
+    TakebackRequested(Color),
+    /// The takeback request was declined or isn't allowed in this game
+    TakebackDeclined,
+    /// A takeback was accepted, `plies` moves were undone which resulted in `fen`
+    ResetPosition {
+        fen: String,
+        plies: usize,
+    },
+    /// The player of this color wants a rematch
+    #[serde(with = "json::color")]
+    RematchOffered(Color),
+    /// Both players want a rematch, it's played in the new lobby `lobby_id` with the same
+    /// sessions and swapped colors
+    Rematch {
+        lobby_id: Uuid,
+    },
+    /// Remaining time of white and black in milliseconds, sent in games with a time control
+    /// after every move and when connecting. If `running` is set, the clock of the side to move
+    /// counts down from the moment the message was sent
+    Clocks {
+        white: u64,
+        black: u64,
+        running: bool,
+    },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Message, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+/// Why a game ended
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Message, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameOverReason {
+    Checkmate,
+    Stalemate,
+    FiftyMoveRule,
+    /// The side to move ran out of time, which is a draw if the opponent can't checkmate
+    Timeout,
 }
 
-#[derive(Debug, Clone, PartialEq, Message)]
+impl fmt::Display for GameOverReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Checkmate => "checkmate",
+            Self::Stalemate => "stalemate",
+            Self::FiftyMoveRule => "the fifty-move rule",
+            Self::Timeout => "timeout",
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RatingChange {
+    /// Rating after the game
+    pub rating: u32,
+    pub delta: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Message, Deserialize, Serialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
 pub enum ClientMsg {
     Connect {
         lobby_id: Uuid,
@@ -32,5 +142,19 @@ pub enum ClientMsg {
         lobby_id: Uuid,
         session: Uuid,
     },
+    #[serde(with = "json::uci_move")]
     PlayMove(Move),
+    /// Waits until the seek is paired, the seek is cancelled when the connection closes
+    WaitForMatch {
+        seek_id: Uuid,
+    },
+    /// Subscribes to [`ServerMsg::LobbyOpened`] and [`ServerMsg::LobbyClosed`]
+    WatchLobbies,
+    /// Asks the opponent to undo the own last move, along with the opponent's reply if they
+    /// already moved
+    RequestTakeback,
+    AcceptTakeback,
+    DeclineTakeback,
+    /// Asks for another game against the same opponent once the game is over
+    OfferRematch,
 }
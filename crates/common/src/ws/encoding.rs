@@ -0,0 +1,206 @@
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::io::{self, ErrorKind};
+
+use super::message::{Limits, Message, Version};
+
+#[cfg(feature = "axum")]
+use axum::extract::ws::Message as AxumMessage;
+
+/// How messages are encoded on a single WebSocket connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// Binary frames containing a [`Message`] of the given version
+    Binary(Version),
+    /// Text frames containing the serde JSON representation
+    Json,
+}
+
+impl Encoding {
+    pub fn encode<T: Message + Serialize>(self, msg: &T) -> io::Result<Vec<u8>> {
+        match self {
+            Encoding::Binary(version) => {
+                let mut buf = Vec::new();
+                msg.encode_versioned(&mut buf, version)?;
+                Ok(buf)
+            }
+            Encoding::Json => serde_json::to_vec(msg).map_err(io::Error::from),
+        }
+    }
+
+    pub fn decode<T: Message + DeserializeOwned>(
+        self,
+        bytes: &[u8],
+        limits: Limits,
+    ) -> io::Result<T> {
+        match self {
+            Encoding::Binary(version) => T::decode_versioned(bytes, version, limits),
+            Encoding::Json => {
+                if bytes.len() > limits.max_message_len {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!(
+                            "message exceeds maximum size of {} bytes",
+                            limits.max_message_len
+                        ),
+                    ));
+                }
+                let value = serde_json::from_slice(bytes)?;
+                check_json(&value, limits, 0)?;
+                serde_json::from_value(value).map_err(io::Error::from)
+            }
+        }
+    }
+
+    /// Picks the encoding matching the frame type of a message, i.e. JSON for text frames.
+    #[cfg(feature = "axum")]
+    pub fn detect_axum(msg: &AxumMessage, version: Version) -> Option<Self> {
+        match msg {
+            AxumMessage::Binary(_) => Some(Encoding::Binary(version)),
+            AxumMessage::Text(_) => Some(Encoding::Json),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "axum")]
+    pub fn encode_axum<T: Message + Serialize>(self, msg: &T) -> io::Result<AxumMessage> {
+        let bytes = self.encode(msg)?;
+        match self {
+            Encoding::Binary(_) => Ok(AxumMessage::Binary(bytes)),
+            Encoding::Json => Ok(AxumMessage::Text(
+                String::from_utf8(bytes).expect("serde_json produces valid UTF-8"),
+            )),
+        }
+    }
+
+    #[cfg(feature = "axum")]
+    pub fn decode_axum<T: Message + DeserializeOwned>(
+        self,
+        msg: AxumMessage,
+        limits: Limits,
+    ) -> io::Result<T> {
+        match (self, msg) {
+            (Encoding::Binary(_), AxumMessage::Binary(bytes)) => self.decode(&bytes, limits),
+            (Encoding::Json, AxumMessage::Text(text)) => self.decode(text.as_bytes(), limits),
+            (_, msg) => Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("invalid message type of {msg:?} for {self:?} encoding"),
+            )),
+        }
+    }
+}
+
+/// Enforces the [`Limits`] that the binary codec checks while decoding on a parsed JSON
+/// message, objects and arrays count as one level of nesting each.
+fn check_json(value: &Value, limits: Limits, depth: usize) -> io::Result<()> {
+    let invalid = |msg: String| Err(io::Error::new(ErrorKind::InvalidData, msg));
+    let check_len = |len: usize| {
+        if len > limits.max_collection_len {
+            invalid(format!(
+                "collection exceeds maximum length of {}",
+                limits.max_collection_len
+            ))
+        } else if depth >= limits.max_depth {
+            invalid(format!(
+                "message exceeds maximum depth of {}",
+                limits.max_depth
+            ))
+        } else {
+            Ok(())
+        }
+    };
+    let check_string = |string: &str| {
+        if string.len() > limits.max_string_len {
+            invalid(format!(
+                "string exceeds maximum length of {} bytes",
+                limits.max_string_len
+            ))
+        } else {
+            Ok(())
+        }
+    };
+
+    match value {
+        Value::Null | Value::Bool(_) | Value::Number(_) => Ok(()),
+        Value::String(string) => check_string(string),
+        Value::Array(values) => {
+            check_len(values.len())?;
+            values
+                .iter()
+                .try_for_each(|value| check_json(value, limits, depth + 1))
+        }
+        Value::Object(fields) => {
+            check_len(fields.len())?;
+            fields.iter().try_for_each(|(key, value)| {
+                check_string(key)?;
+                check_json(value, limits, depth + 1)
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ws::ClientMsg;
+
+    fn decode(json: &str, limits: Limits) -> io::Result<ClientMsg> {
+        Encoding::Json.decode(json.as_bytes(), limits)
+    }
+
+    #[test]
+    fn json_string_limits_are_enforced() {
+        let limits = Limits {
+            max_string_len: 9,
+            ..Limits::default()
+        };
+        assert!(decode(r#"{"type": "play_move", "data": "e2e4"}"#, limits).is_ok());
+        assert!(decode(
+            r#"{"type": "play_move", "data": "e2e4", "x": "0123456789"}"#,
+            limits
+        )
+        .is_err());
+        assert!(decode(
+            r#"{"type": "play_move", "data": "e2e4", "extra_data": 0}"#,
+            limits
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn json_collection_limits_are_enforced() {
+        let limits = Limits {
+            max_collection_len: 2,
+            ..Limits::default()
+        };
+        assert!(decode(r#"{"type": "play_move", "data": "e2e4"}"#, limits).is_ok());
+        assert!(decode(
+            r#"{"type": "play_move", "data": "e2e4", "x": [1, 2, 3]}"#,
+            limits
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn json_depth_limits_are_enforced() {
+        let limits = Limits {
+            max_depth: 2,
+            ..Limits::default()
+        };
+        assert!(decode(r#"{"type": "play_move", "data": "e2e4"}"#, limits).is_ok());
+        assert!(decode(
+            r#"{"type": "play_move", "data": "e2e4", "x": [[0]]}"#,
+            limits
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn json_size_limits_are_enforced() {
+        let limits = Limits {
+            max_message_len: 16,
+            ..Limits::default()
+        };
+        assert!(decode(r#"{"type": "play_move", "data": "e2e4"}"#, limits).is_err());
+    }
+}
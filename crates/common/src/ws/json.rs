@@ -0,0 +1,38 @@
+//! Serde helpers for the JSON representation of chess types that don't implement serde themselves.
+
+/// (De)serializes a [`cozy_chess::Move`] as a UCI string like `e7e8q`.
+pub mod uci_move {
+    use cozy_chess::Move;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(chess_move: &Move, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(chess_move)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Move, D::Error> {
+        let str = String::deserialize(deserializer)?;
+        str.parse()
+            .map_err(|_| de::Error::custom(format!("invalid move '{str}'")))
+    }
+}
+
+/// (De)serializes a [`cozy_chess::Color`] as `"white"` or `"black"`.
+pub mod color {
+    use cozy_chess::Color;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(color: &Color, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(match color {
+            Color::White => "white",
+            Color::Black => "black",
+        })
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        match String::deserialize(deserializer)?.as_str() {
+            "white" => Ok(Color::White),
+            "black" => Ok(Color::Black),
+            other => Err(de::Error::unknown_variant(other, &["white", "black"])),
+        }
+    }
+}
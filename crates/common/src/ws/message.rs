@@ -1,5 +1,14 @@
-use cozy_chess::{Color, Move, Piece, Square};
-use std::io::{self, ErrorKind, Read, Write};
+use crate::{http::TimeControl, ws::RatingChange};
+use cozy_chess::{
+    BitBoard, Board, BoardBuilder, CastleRights, Color, File, Move, Piece, Rank, Square,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    io::{self, ErrorKind, Read, Write},
+    time::Duration,
+};
 use uuid::Uuid;
 
 #[cfg(feature = "reqwasm")]
@@ -8,22 +17,294 @@ use reqwasm::websocket::Message as ReqwasmMessage;
 #[cfg(feature = "axum")]
 use axum::extract::ws::Message as AxumMessage;
 
+/// Upper bounds that are enforced while decoding a message.
+///
+/// Messages usually come from untrusted peers, so every length prefix is
+/// checked against these before anything gets allocated.
+/// JSON messages are checked against the same limits once they are parsed,
+/// the message size limits how much gets allocated before that.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum length of a single string in bytes
+    pub max_string_len: usize,
+    /// Maximum size of a whole message in bytes
+    pub max_message_len: usize,
+    /// Maximum number of elements in a single collection
+    pub max_collection_len: usize,
+    /// Maximum nesting depth of enums, options and collections
+    pub max_depth: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_string_len: 1024,
+            max_message_len: 64 * 1024,
+            max_collection_len: 4096,
+            max_depth: 32,
+        }
+    }
+}
+
+/// Wire format revision used by the binary codec.
+///
+/// [`Version::V1`] encodes every integer as a fixed-width little-endian `u64`,
+/// [`Version::V2`] uses LEB128 varints and packs chess types into as few bytes as possible.
+/// Both sides of a connection have to agree on the version.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize,
+)]
+#[serde(try_from = "u8", into = "u8")]
+pub enum Version {
+    V1,
+    #[default]
+    V2,
+}
+
+impl Version {
+    pub const CURRENT: Version = Version::V2;
+}
+
+impl TryFrom<u8> for Version {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Version::V1),
+            2 => Ok(Version::V2),
+            other => Err(format!("unknown protocol version {other}")),
+        }
+    }
+}
+
+impl From<Version> for u8 {
+    fn from(value: Version) -> Self {
+        match value {
+            Version::V1 => 1,
+            Version::V2 => 2,
+        }
+    }
+}
+
+/// State that is threaded through [`Message::encode_with`].
+#[derive(Debug)]
+pub struct EncodeContext<W> {
+    write: W,
+    version: Version,
+}
+
+impl<W: Write> EncodeContext<W> {
+    pub fn new(write: W, version: Version) -> Self {
+        Self { write, version }
+    }
+
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    pub fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.write.write_all(buf)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.write.flush()
+    }
+
+    /// Writes `val` as an unsigned LEB128 varint.
+    pub fn write_varint(&mut self, mut val: u64) -> io::Result<()> {
+        let mut buf = [0; 10];
+        let mut len = 0;
+        loop {
+            let byte = (val & 0x7f) as u8;
+            val >>= 7;
+            if val == 0 {
+                buf[len] = byte;
+                len += 1;
+                break;
+            }
+            buf[len] = byte | 0x80;
+            len += 1;
+        }
+        self.write_all(&buf[..len])
+    }
+}
+
+/// State that is threaded through [`Message::decode_with`].
+///
+/// Keeps track of how many bytes were read and how deeply nested the
+/// currently decoded value is, and fails once one of the [`Limits`] is exceeded.
+#[derive(Debug)]
+pub struct DecodeContext<R> {
+    read: R,
+    version: Version,
+    limits: Limits,
+    consumed: usize,
+    depth: usize,
+}
+
+impl<R: Read> DecodeContext<R> {
+    pub fn new(read: R, version: Version, limits: Limits) -> Self {
+        Self {
+            read,
+            version,
+            limits,
+            consumed: 0,
+            depth: 0,
+        }
+    }
+
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Number of bytes that may still be read before the message size limit is hit.
+    pub fn remaining(&self) -> usize {
+        self.limits.max_message_len - self.consumed
+    }
+
+    pub fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        if buf.len() > self.remaining() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "message exceeds maximum size of {} bytes",
+                    self.limits.max_message_len
+                ),
+            ));
+        }
+        self.read.read_exact(buf)?;
+        self.consumed += buf.len();
+        Ok(())
+    }
+
+    /// Decodes a value one nesting level deeper than the current one.
+    pub fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> io::Result<T>) -> io::Result<T> {
+        if self.depth >= self.limits.max_depth {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("message exceeds maximum depth of {}", self.limits.max_depth),
+            ));
+        }
+        self.depth += 1;
+        let res = f(self);
+        self.depth -= 1;
+        res
+    }
+
+    /// Reads an unsigned LEB128 varint, rejecting overlong encodings.
+    pub fn read_varint(&mut self) -> io::Result<u64> {
+        let mut val = 0u64;
+        for idx in 0..10 {
+            let mut buf = [0];
+            self.read_exact(&mut buf)?;
+            let byte = buf[0];
+            let bits = u64::from(byte & 0x7f);
+            if idx == 9 && bits > 1 {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "varint overflows u64",
+                ));
+            }
+            val |= bits << (7 * idx);
+            if byte & 0x80 == 0 {
+                if byte == 0 && idx > 0 {
+                    return Err(io::Error::new(ErrorKind::InvalidData, "overlong varint"));
+                }
+                return Ok(val);
+            }
+        }
+        Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "varint overflows u64",
+        ))
+    }
+
+    /// Reads a collection length and checks it against [`Limits::max_collection_len`].
+    pub fn read_len(&mut self) -> io::Result<usize> {
+        let len = usize::decode_with(self)?;
+        if len > self.limits.max_collection_len {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "collection length {len} exceeds maximum of {}",
+                    self.limits.max_collection_len
+                ),
+            ));
+        }
+        Ok(len)
+    }
+
+    /// Makes sure there is no data left after a complete message.
+    pub fn finish(mut self) -> io::Result<()> {
+        let mut buf = [0];
+        match self.read.read(&mut buf)? {
+            0 => Ok(()),
+            _ => Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "trailing bytes after message",
+            )),
+        }
+    }
+}
+
 pub trait Message: Sized {
-    fn encode(&self, write: impl Write) -> io::Result<()>;
+    fn encode_with<W: Write>(&self, ctx: &mut EncodeContext<W>) -> io::Result<()>;
 
-    fn decode(read: impl Read) -> io::Result<Self>;
+    fn decode_with<R: Read>(ctx: &mut DecodeContext<R>) -> io::Result<Self>;
+
+    /// Encodes a complete message using [`Version::CURRENT`].
+    fn encode(&self, write: impl Write) -> io::Result<()> {
+        self.encode_versioned(write, Version::CURRENT)
+    }
+
+    fn encode_versioned(&self, write: impl Write, version: Version) -> io::Result<()> {
+        let mut ctx = EncodeContext::new(write, version);
+        self.encode_with(&mut ctx)?;
+        ctx.flush()
+    }
+
+    /// Decodes a complete message using [`Version::CURRENT`] and the default [`Limits`].
+    fn decode(read: impl Read) -> io::Result<Self> {
+        Self::decode_versioned(read, Version::CURRENT, Limits::default())
+    }
+
+    /// Decodes a complete message, failing if there are any trailing bytes.
+    fn decode_versioned(read: impl Read, version: Version, limits: Limits) -> io::Result<Self> {
+        let mut ctx = DecodeContext::new(read, version, limits);
+        let val = Self::decode_with(&mut ctx)?;
+        ctx.finish()?;
+        Ok(val)
+    }
 
     #[cfg(feature = "axum")]
     fn to_axum_message(&self) -> io::Result<AxumMessage> {
+        self.to_axum_message_versioned(Version::CURRENT)
+    }
+
+    #[cfg(feature = "axum")]
+    fn to_axum_message_versioned(&self, version: Version) -> io::Result<AxumMessage> {
         let mut buf = Vec::new();
-        self.encode(&mut buf)?;
+        self.encode_versioned(&mut buf, version)?;
         Ok(AxumMessage::Binary(buf))
     }
 
     #[cfg(feature = "axum")]
     fn from_axum_message(msg: AxumMessage) -> io::Result<Self> {
+        Self::from_axum_message_versioned(msg, Version::CURRENT, Limits::default())
+    }
+
+    #[cfg(feature = "axum")]
+    fn from_axum_message_versioned(
+        msg: AxumMessage,
+        version: Version,
+        limits: Limits,
+    ) -> io::Result<Self> {
         match msg {
-            AxumMessage::Binary(bytes) => Self::decode(&bytes[..]),
+            AxumMessage::Binary(bytes) => Self::decode_versioned(&bytes[..], version, limits),
             _ => Err(io::Error::new(
                 ErrorKind::InvalidData,
                 format!("invalid message type of {msg:?}"),
@@ -51,49 +332,44 @@ pub trait Message: Sized {
 }
 
 impl Message for Uuid {
-    fn encode(&self, mut write: impl Write) -> io::Result<()> {
-        write.write_all(self.as_bytes())?;
-        write.flush()
+    fn encode_with<W: Write>(&self, ctx: &mut EncodeContext<W>) -> io::Result<()> {
+        ctx.write_all(self.as_bytes())
     }
 
-    fn decode(mut read: impl Read) -> io::Result<Self> {
+    fn decode_with<R: Read>(ctx: &mut DecodeContext<R>) -> io::Result<Self> {
         let mut buf = [0; 16];
-        read.read_exact(&mut buf)?;
+        ctx.read_exact(&mut buf)?;
         Ok(Uuid::from_bytes(buf))
     }
 }
 
 impl Message for u8 {
-    fn encode(&self, mut write: impl Write) -> io::Result<()> {
-        write.write_all(&[*self])?;
-        write.flush()
+    fn encode_with<W: Write>(&self, ctx: &mut EncodeContext<W>) -> io::Result<()> {
+        ctx.write_all(&[*self])
     }
 
-    fn decode(mut read: impl Read) -> io::Result<Self> {
+    fn decode_with<R: Read>(ctx: &mut DecodeContext<R>) -> io::Result<Self> {
         let mut buf = [0];
-        read.read_exact(&mut buf)?;
+        ctx.read_exact(&mut buf)?;
         Ok(buf[0])
     }
 }
 
 impl<T: Message> Message for Option<T> {
-    fn encode(&self, mut write: impl Write) -> io::Result<()> {
+    fn encode_with<W: Write>(&self, ctx: &mut EncodeContext<W>) -> io::Result<()> {
         match self {
             Some(val) => {
-                write.write_all(&[0])?;
-                val.encode(&mut write)?;
-            }
-            None => {
-                write.write_all(&[1])?;
+                ctx.write_all(&[0])?;
+                val.encode_with(ctx)
             }
+            None => ctx.write_all(&[1]),
         }
-        write.flush()
     }
 
-    fn decode(mut read: impl Read) -> io::Result<Self> {
-        let id = u8::decode(&mut read)?;
+    fn decode_with<R: Read>(ctx: &mut DecodeContext<R>) -> io::Result<Self> {
+        let id = u8::decode_with(ctx)?;
         match id {
-            0 => Ok(Some(T::decode(&mut read)?)),
+            0 => Ok(Some(ctx.nested(T::decode_with)?)),
             1 => Ok(None),
             _ => Err(io::Error::new(
                 ErrorKind::InvalidData,
@@ -103,15 +379,28 @@ impl<T: Message> Message for Option<T> {
     }
 }
 
+/// Encodes the index of a small enum, as a `usize` in [`Version::V1`] and a single byte otherwise.
+fn encode_index<W: Write>(idx: usize, ctx: &mut EncodeContext<W>) -> io::Result<()> {
+    match ctx.version() {
+        Version::V1 => idx.encode_with(ctx),
+        Version::V2 => (idx as u8).encode_with(ctx),
+    }
+}
+
+fn decode_index<R: Read>(ctx: &mut DecodeContext<R>) -> io::Result<usize> {
+    match ctx.version() {
+        Version::V1 => usize::decode_with(ctx),
+        Version::V2 => u8::decode_with(ctx).map(usize::from),
+    }
+}
+
 impl Message for Square {
-    fn encode(&self, mut write: impl Write) -> io::Result<()> {
-        let id = *self as usize;
-        id.encode(&mut write)?;
-        write.flush()
+    fn encode_with<W: Write>(&self, ctx: &mut EncodeContext<W>) -> io::Result<()> {
+        encode_index(*self as usize, ctx)
     }
 
-    fn decode(mut read: impl Read) -> io::Result<Self> {
-        let id = usize::decode(&mut read)?;
+    fn decode_with<R: Read>(ctx: &mut DecodeContext<R>) -> io::Result<Self> {
+        let id = decode_index(ctx)?;
         Square::try_index(id).ok_or_else(|| {
             io::Error::new(
                 ErrorKind::InvalidData,
@@ -122,95 +411,685 @@ impl Message for Square {
 }
 
 impl Message for Piece {
-    fn encode(&self, mut write: impl Write) -> io::Result<()> {
-        let id = *self as usize;
-        id.encode(&mut write)?;
-        write.flush()
+    fn encode_with<W: Write>(&self, ctx: &mut EncodeContext<W>) -> io::Result<()> {
+        encode_index(*self as usize, ctx)
     }
 
-    fn decode(mut read: impl Read) -> io::Result<Self> {
-        let id = usize::decode(&mut read)?;
+    fn decode_with<R: Read>(ctx: &mut DecodeContext<R>) -> io::Result<Self> {
+        let id = decode_index(ctx)?;
         Piece::try_index(id)
             .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, format!("piece id {id}")))
     }
 }
 
 impl Message for Move {
-    fn encode(&self, mut write: impl Write) -> io::Result<()> {
-        self.from.encode(&mut write)?;
-        self.to.encode(&mut write)?;
-        self.promotion.encode(&mut write)?;
-        write.flush()
-    }
-
-    fn decode(mut read: impl Read) -> io::Result<Self> {
-        let from = Square::decode(&mut read)?;
-        let to = Square::decode(&mut read)?;
-        let promotion = Option::decode(&mut read)?;
-
-        Ok(Move {
-            from,
-            to,
-            promotion,
-        })
+    /// In [`Version::V2`] a move is packed into a little-endian `u16`: bits 0-5 hold the
+    /// source square, bits 6-11 the destination square and bits 12-14 the promotion piece
+    /// plus one (zero meaning no promotion).
+    fn encode_with<W: Write>(&self, ctx: &mut EncodeContext<W>) -> io::Result<()> {
+        match ctx.version() {
+            Version::V1 => {
+                self.from.encode_with(ctx)?;
+                self.to.encode_with(ctx)?;
+                self.promotion.encode_with(ctx)
+            }
+            Version::V2 => {
+                let promotion = self.promotion.map_or(0, |piece| piece as u16 + 1);
+                let packed = self.from as u16 | (self.to as u16) << 6 | promotion << 12;
+                ctx.write_all(&packed.to_le_bytes())
+            }
+        }
+    }
+
+    fn decode_with<R: Read>(ctx: &mut DecodeContext<R>) -> io::Result<Self> {
+        match ctx.version() {
+            Version::V1 => {
+                let from = Square::decode_with(ctx)?;
+                let to = Square::decode_with(ctx)?;
+                let promotion = Option::decode_with(ctx)?;
+
+                Ok(Move {
+                    from,
+                    to,
+                    promotion,
+                })
+            }
+            Version::V2 => {
+                let mut buf = [0; 2];
+                ctx.read_exact(&mut buf)?;
+                let packed = u16::from_le_bytes(buf);
+                if packed >> 15 != 0 {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!("packed move {packed:#06x}"),
+                    ));
+                }
+                let from = Square::index(usize::from(packed & 0x3f));
+                let to = Square::index(usize::from(packed >> 6 & 0x3f));
+                let promotion = match usize::from(packed >> 12 & 0x7) {
+                    0 => None,
+                    id => Some(Piece::try_index(id - 1).ok_or_else(|| {
+                        io::Error::new(ErrorKind::InvalidData, format!("piece id {}", id - 1))
+                    })?),
+                };
+
+                Ok(Move {
+                    from,
+                    to,
+                    promotion,
+                })
+            }
+        }
     }
 }
 
 impl Message for Color {
-    fn encode(&self, mut write: impl Write) -> io::Result<()> {
-        let id = *self as usize;
-        id.encode(&mut write)?;
-        write.flush()
+    fn encode_with<W: Write>(&self, ctx: &mut EncodeContext<W>) -> io::Result<()> {
+        encode_index(*self as usize, ctx)
     }
 
-    fn decode(mut read: impl Read) -> io::Result<Self> {
-        let id = usize::decode(&mut read)?;
+    fn decode_with<R: Read>(ctx: &mut DecodeContext<R>) -> io::Result<Self> {
+        let id = decode_index(ctx)?;
         Color::try_index(id)
             .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, format!("color id {id}")))
     }
 }
 
 impl Message for usize {
-    fn encode(&self, mut write: impl Write) -> io::Result<()> {
+    fn encode_with<W: Write>(&self, ctx: &mut EncodeContext<W>) -> io::Result<()> {
         let a = *self as u64;
-        a.encode(&mut write)?;
-        write.flush()
+        a.encode_with(ctx)
     }
 
-    fn decode(mut read: impl Read) -> io::Result<Self> {
-        let a = u64::decode(&mut read)?;
-        Ok(a as usize)
+    fn decode_with<R: Read>(ctx: &mut DecodeContext<R>) -> io::Result<Self> {
+        let a = u64::decode_with(ctx)?;
+        usize::try_from(a).map_err(|err| io::Error::new(ErrorKind::InvalidData, err))
     }
 }
 
 impl Message for u64 {
-    fn encode(&self, mut write: impl Write) -> io::Result<()> {
-        write.write_all(&self.to_le_bytes())?;
-        write.flush()
+    fn encode_with<W: Write>(&self, ctx: &mut EncodeContext<W>) -> io::Result<()> {
+        match ctx.version() {
+            Version::V1 => ctx.write_all(&self.to_le_bytes()),
+            Version::V2 => ctx.write_varint(*self),
+        }
     }
 
-    fn decode(mut read: impl Read) -> io::Result<Self> {
-        let mut buf = [0; 8];
-        read.read_exact(&mut buf)?;
-        Ok(Self::from_le_bytes(buf))
+    fn decode_with<R: Read>(ctx: &mut DecodeContext<R>) -> io::Result<Self> {
+        match ctx.version() {
+            Version::V1 => {
+                let mut buf = [0; 8];
+                ctx.read_exact(&mut buf)?;
+                Ok(Self::from_le_bytes(buf))
+            }
+            Version::V2 => ctx.read_varint(),
+        }
     }
 }
 
 impl Message for String {
-    fn encode(&self, mut write: impl Write) -> io::Result<()> {
-        let len = self.len() as u64;
-        len.encode(&mut write)?;
-        write.write_all(self.as_bytes())?;
-        write.flush()
+    fn encode_with<W: Write>(&self, ctx: &mut EncodeContext<W>) -> io::Result<()> {
+        self.len().encode_with(ctx)?;
+        ctx.write_all(self.as_bytes())
     }
 
-    fn decode(mut read: impl Read) -> io::Result<Self> {
-        let len = match usize::try_from(u64::decode(&mut read)?) {
-            Ok(val) => val,
-            Err(err) => return Err(io::Error::new(ErrorKind::InvalidData, err)),
-        };
+    fn decode_with<R: Read>(ctx: &mut DecodeContext<R>) -> io::Result<Self> {
+        let len = usize::decode_with(ctx)?;
+        let max_len = ctx.limits().max_string_len.min(ctx.remaining());
+        if len > max_len {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("string length {len} exceeds maximum of {max_len}"),
+            ));
+        }
         let mut buf = vec![0; len];
-        read.read_exact(&mut buf[..])?;
+        ctx.read_exact(&mut buf[..])?;
         String::from_utf8(buf).map_err(|err| io::Error::new(ErrorKind::InvalidData, err))
     }
 }
+
+impl Message for bool {
+    fn encode_with<W: Write>(&self, ctx: &mut EncodeContext<W>) -> io::Result<()> {
+        u8::from(*self).encode_with(ctx)
+    }
+
+    fn decode_with<R: Read>(ctx: &mut DecodeContext<R>) -> io::Result<Self> {
+        match u8::decode_with(ctx)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            id => Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("bool id {id}"),
+            )),
+        }
+    }
+}
+
+/// Implements [`Message`] for an unsigned integer that is encoded with its native width
+/// in [`Version::V1`] and as a varint otherwise.
+macro_rules! impl_message_uint {
+    ($($ty:ty),*) => {
+        $(
+            impl Message for $ty {
+                fn encode_with<W: Write>(&self, ctx: &mut EncodeContext<W>) -> io::Result<()> {
+                    match ctx.version() {
+                        Version::V1 => ctx.write_all(&self.to_le_bytes()),
+                        Version::V2 => ctx.write_varint(u64::from(*self)),
+                    }
+                }
+
+                fn decode_with<R: Read>(ctx: &mut DecodeContext<R>) -> io::Result<Self> {
+                    match ctx.version() {
+                        Version::V1 => {
+                            let mut buf = [0; std::mem::size_of::<$ty>()];
+                            ctx.read_exact(&mut buf)?;
+                            Ok(Self::from_le_bytes(buf))
+                        }
+                        Version::V2 => {
+                            let val = ctx.read_varint()?;
+                            Self::try_from(val)
+                                .map_err(|err| io::Error::new(ErrorKind::InvalidData, err.to_string()))
+                        }
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_message_uint!(u16, u32);
+
+impl Message for i64 {
+    /// Uses zigzag encoding in [`Version::V2`], so small negative numbers stay small.
+    fn encode_with<W: Write>(&self, ctx: &mut EncodeContext<W>) -> io::Result<()> {
+        match ctx.version() {
+            Version::V1 => ctx.write_all(&self.to_le_bytes()),
+            Version::V2 => ctx.write_varint(((self << 1) ^ (self >> 63)) as u64),
+        }
+    }
+
+    fn decode_with<R: Read>(ctx: &mut DecodeContext<R>) -> io::Result<Self> {
+        match ctx.version() {
+            Version::V1 => {
+                let mut buf = [0; 8];
+                ctx.read_exact(&mut buf)?;
+                Ok(Self::from_le_bytes(buf))
+            }
+            Version::V2 => {
+                let val = ctx.read_varint()?;
+                Ok((val >> 1) as i64 ^ -((val & 1) as i64))
+            }
+        }
+    }
+}
+
+impl Message for f64 {
+    fn encode_with<W: Write>(&self, ctx: &mut EncodeContext<W>) -> io::Result<()> {
+        ctx.write_all(&self.to_le_bytes())
+    }
+
+    fn decode_with<R: Read>(ctx: &mut DecodeContext<R>) -> io::Result<Self> {
+        let mut buf = [0; 8];
+        ctx.read_exact(&mut buf)?;
+        Ok(Self::from_le_bytes(buf))
+    }
+}
+
+impl<T: Message> Message for Box<T> {
+    fn encode_with<W: Write>(&self, ctx: &mut EncodeContext<W>) -> io::Result<()> {
+        (**self).encode_with(ctx)
+    }
+
+    fn decode_with<R: Read>(ctx: &mut DecodeContext<R>) -> io::Result<Self> {
+        ctx.nested(T::decode_with).map(Box::new)
+    }
+}
+
+impl<T: Message> Message for Vec<T> {
+    fn encode_with<W: Write>(&self, ctx: &mut EncodeContext<W>) -> io::Result<()> {
+        self.len().encode_with(ctx)?;
+        for val in self {
+            val.encode_with(ctx)?;
+        }
+        Ok(())
+    }
+
+    fn decode_with<R: Read>(ctx: &mut DecodeContext<R>) -> io::Result<Self> {
+        let len = ctx.read_len()?;
+        ctx.nested(|ctx| {
+            let mut vec = Vec::with_capacity(len.min(ctx.remaining()));
+            for _ in 0..len {
+                vec.push(T::decode_with(ctx)?);
+            }
+            Ok(vec)
+        })
+    }
+}
+
+impl<T: Message, const N: usize> Message for [T; N] {
+    fn encode_with<W: Write>(&self, ctx: &mut EncodeContext<W>) -> io::Result<()> {
+        for val in self {
+            val.encode_with(ctx)?;
+        }
+        Ok(())
+    }
+
+    fn decode_with<R: Read>(ctx: &mut DecodeContext<R>) -> io::Result<Self> {
+        ctx.nested(|ctx| {
+            let mut vec = Vec::with_capacity(N);
+            for _ in 0..N {
+                vec.push(T::decode_with(ctx)?);
+            }
+            Ok(vec)
+        })
+        .map(|vec| match vec.try_into() {
+            Ok(arr) => arr,
+            Err(_) => unreachable!("decoded exactly {N} elements"),
+        })
+    }
+}
+
+impl<K: Message + Eq + Hash, V: Message> Message for HashMap<K, V> {
+    fn encode_with<W: Write>(&self, ctx: &mut EncodeContext<W>) -> io::Result<()> {
+        self.len().encode_with(ctx)?;
+        for (key, val) in self {
+            key.encode_with(ctx)?;
+            val.encode_with(ctx)?;
+        }
+        Ok(())
+    }
+
+    fn decode_with<R: Read>(ctx: &mut DecodeContext<R>) -> io::Result<Self> {
+        let len = ctx.read_len()?;
+        ctx.nested(|ctx| {
+            let mut map = HashMap::with_capacity(len.min(ctx.remaining()));
+            for _ in 0..len {
+                let key = K::decode_with(ctx)?;
+                let val = V::decode_with(ctx)?;
+                if map.insert(key, val).is_some() {
+                    return Err(io::Error::new(ErrorKind::InvalidData, "duplicate map key"));
+                }
+            }
+            Ok(map)
+        })
+    }
+}
+
+/// Implements [`Message`] for tuples by encoding their elements in order.
+macro_rules! impl_message_tuple {
+    ($(($($name:ident),+)),*) => {
+        $(
+            impl<$($name: Message),+> Message for ($($name,)+) {
+                #[allow(non_snake_case)]
+                fn encode_with<W: Write>(&self, ctx: &mut EncodeContext<W>) -> io::Result<()> {
+                    let ($($name,)+) = self;
+                    $($name.encode_with(ctx)?;)+
+                    Ok(())
+                }
+
+                fn decode_with<R: Read>(ctx: &mut DecodeContext<R>) -> io::Result<Self> {
+                    ctx.nested(|ctx| Ok(($($name::decode_with(ctx)?,)+)))
+                }
+            }
+        )*
+    };
+}
+
+impl_message_tuple!(
+    (A),
+    (A, B),
+    (A, B, C),
+    (A, B, C, D),
+    (A, B, C, D, E),
+    (A, B, C, D, E, F)
+);
+
+impl Message for Duration {
+    fn encode_with<W: Write>(&self, ctx: &mut EncodeContext<W>) -> io::Result<()> {
+        self.as_secs().encode_with(ctx)?;
+        self.subsec_nanos().encode_with(ctx)
+    }
+
+    fn decode_with<R: Read>(ctx: &mut DecodeContext<R>) -> io::Result<Self> {
+        let secs = u64::decode_with(ctx)?;
+        let nanos = u32::decode_with(ctx)?;
+        if nanos >= 1_000_000_000 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("duration nanos {nanos}"),
+            ));
+        }
+        Ok(Duration::new(secs, nanos))
+    }
+}
+
+impl Message for RatingChange {
+    fn encode_with<W: Write>(&self, ctx: &mut EncodeContext<W>) -> io::Result<()> {
+        self.rating.encode_with(ctx)?;
+        self.delta.encode_with(ctx)
+    }
+
+    fn decode_with<R: Read>(ctx: &mut DecodeContext<R>) -> io::Result<Self> {
+        Ok(Self {
+            rating: u32::decode_with(ctx)?,
+            delta: i64::decode_with(ctx)?,
+        })
+    }
+}
+
+impl Message for TimeControl {
+    fn encode_with<W: Write>(&self, ctx: &mut EncodeContext<W>) -> io::Result<()> {
+        self.initial.encode_with(ctx)?;
+        self.increment.encode_with(ctx)
+    }
+
+    fn decode_with<R: Read>(ctx: &mut DecodeContext<R>) -> io::Result<Self> {
+        Ok(Self {
+            initial: u32::decode_with(ctx)?,
+            increment: u32::decode_with(ctx)?,
+        })
+    }
+}
+
+/// Encodes an optional file as a nibble, zero meaning `None`.
+fn file_nibble(file: Option<File>) -> u8 {
+    file.map_or(0, |file| file as u8 + 1)
+}
+
+fn nibble_file(nibble: u8) -> io::Result<Option<File>> {
+    match nibble {
+        0 => Ok(None),
+        id => File::try_index(usize::from(id - 1))
+            .map(Some)
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, format!("file id {}", id - 1))),
+    }
+}
+
+impl Message for Board {
+    /// A board is encoded as its occupancy bitboard followed by one nibble per occupied
+    /// square (piece index in the lower three bits, color in the upper one), then the side
+    /// to move, castle rights, en passant file, halfmove clock and fullmove number.
+    fn encode_with<W: Write>(&self, ctx: &mut EncodeContext<W>) -> io::Result<()> {
+        let occupied = self.occupied();
+        ctx.write_all(&occupied.0.to_le_bytes())?;
+        let nibbles: Vec<u8> = occupied
+            .into_iter()
+            .map(|square| {
+                let piece = self.piece_on(square).unwrap() as u8;
+                let color = self.color_on(square).unwrap() as u8;
+                piece | color << 3
+            })
+            .collect();
+        let packed: Vec<u8> = nibbles
+            .chunks(2)
+            .map(|chunk| chunk[0] | chunk.get(1).map_or(0, |high| high << 4))
+            .collect();
+        ctx.write_all(&packed)?;
+        self.side_to_move().encode_with(ctx)?;
+        for color in Color::ALL {
+            let CastleRights { short, long } = *self.castle_rights(color);
+            (file_nibble(short) | file_nibble(long) << 4).encode_with(ctx)?;
+        }
+        file_nibble(self.en_passant()).encode_with(ctx)?;
+        self.halfmove_clock().encode_with(ctx)?;
+        self.fullmove_number().encode_with(ctx)
+    }
+
+    fn decode_with<R: Read>(ctx: &mut DecodeContext<R>) -> io::Result<Self> {
+        let mut buf = [0; 8];
+        ctx.read_exact(&mut buf)?;
+        let occupied = BitBoard(u64::from_le_bytes(buf));
+        let mut packed = vec![0; (occupied.len() as usize).div_ceil(2)];
+        ctx.read_exact(&mut packed)?;
+
+        let mut builder = BoardBuilder::empty();
+        for (idx, square) in occupied.into_iter().enumerate() {
+            let nibble = packed[idx / 2] >> (idx % 2 * 4) & 0xf;
+            let piece = Piece::try_index(usize::from(nibble & 0x7)).ok_or_else(|| {
+                io::Error::new(ErrorKind::InvalidData, format!("piece id {}", nibble & 0x7))
+            })?;
+            let color = Color::index(usize::from(nibble >> 3));
+            *builder.square_mut(square) = Some((piece, color));
+        }
+        builder.side_to_move = Color::decode_with(ctx)?;
+        for color in Color::ALL {
+            let rights = u8::decode_with(ctx)?;
+            *builder.castle_rights_mut(color) = CastleRights {
+                short: nibble_file(rights & 0xf)?,
+                long: nibble_file(rights >> 4)?,
+            };
+        }
+        let en_passant_rank = Rank::Third.relative_to(!builder.side_to_move);
+        builder.en_passant =
+            nibble_file(u8::decode_with(ctx)?)?.map(|file| Square::new(file, en_passant_rank));
+        builder.halfmove_clock = u8::decode_with(ctx)?;
+        builder.fullmove_number = u16::decode_with(ctx)?;
+
+        builder
+            .build()
+            .map_err(|err| io::Error::new(ErrorKind::InvalidData, err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::{collection, option, prelude::*};
+    use std::fmt::Debug;
+
+    const VERSIONS: [Version; 2] = [Version::V1, Version::V2];
+
+    fn encode<T: Message>(val: &T, version: Version) -> Vec<u8> {
+        let mut buf = Vec::new();
+        val.encode_versioned(&mut buf, version).unwrap();
+        buf
+    }
+
+    fn decode<T: Message>(buf: &[u8], version: Version) -> io::Result<T> {
+        T::decode_versioned(buf, version, Limits::default())
+    }
+
+    fn round_trip<T: Message + PartialEq + Debug>(val: &T) -> Result<(), TestCaseError> {
+        for version in VERSIONS {
+            let decoded: T = decode(&encode(val, version), version)
+                .map_err(|err| TestCaseError::fail(format!("{version:?}: {err}")))?;
+            prop_assert_eq!(&decoded, val, "{:?}", version);
+        }
+        Ok(())
+    }
+
+    fn square() -> impl Strategy<Value = Square> {
+        (0..Square::NUM).prop_map(Square::index)
+    }
+
+    fn chess_move() -> impl Strategy<Value = Move> {
+        (
+            square(),
+            square(),
+            option::of((0..Piece::NUM).prop_map(Piece::index)),
+        )
+            .prop_map(|(from, to, promotion)| Move {
+                from,
+                to,
+                promotion,
+            })
+    }
+
+    /// Positions reached by playing random legal moves from a standard or Chess960 start
+    fn board() -> impl Strategy<Value = Board> {
+        (
+            option::of(0..960u32),
+            collection::vec(any::<usize>(), 0..80),
+        )
+            .prop_map(|(chess960, choices)| {
+                let mut board = chess960.map_or_else(Board::default, Board::chess960_startpos);
+                for choice in choices {
+                    let mut moves = Vec::new();
+                    board.generate_moves(|piece_moves| {
+                        moves.extend(piece_moves);
+                        false
+                    });
+                    if moves.is_empty() {
+                        break;
+                    }
+                    board.play_unchecked(moves[choice % moves.len()]);
+                }
+                board
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn primitives_round_trip(
+            a in any::<u8>(),
+            b in any::<u16>(),
+            c in any::<u32>(),
+            d in any::<u64>(),
+            e in any::<usize>(),
+            f in any::<i64>(),
+            g in any::<bool>(),
+        ) {
+            round_trip(&a)?;
+            round_trip(&b)?;
+            round_trip(&c)?;
+            round_trip(&d)?;
+            round_trip(&e)?;
+            round_trip(&f)?;
+            round_trip(&g)?;
+        }
+
+        #[test]
+        fn f64_round_trips_bitwise(val in any::<f64>()) {
+            for version in VERSIONS {
+                let decoded: f64 = decode(&encode(&val, version), version).unwrap();
+                prop_assert_eq!(decoded.to_bits(), val.to_bits());
+            }
+        }
+
+        #[test]
+        fn small_i64_stay_small(val in -64i64..64) {
+            prop_assert_eq!(encode(&val, Version::V2).len(), 1);
+        }
+
+        #[test]
+        fn strings_and_uuids_round_trip(string in ".{0,64}", uuid in any::<u128>()) {
+            round_trip(&string)?;
+            round_trip(&Uuid::from_u128(uuid))?;
+        }
+
+        #[test]
+        fn collections_round_trip(
+            vec in collection::vec(any::<u32>(), 0..64),
+            nested in collection::vec(collection::vec(any::<String>(), 0..4), 0..4),
+            map in collection::hash_map(any::<u16>(), any::<i64>(), 0..32),
+            boxed in any::<Option<u8>>().prop_map(Box::new),
+            array in any::<[u64; 4]>(),
+        ) {
+            round_trip(&vec)?;
+            round_trip(&nested)?;
+            round_trip(&map)?;
+            round_trip(&boxed)?;
+            round_trip(&Some(boxed))?;
+            round_trip(&array)?;
+        }
+
+        #[test]
+        fn tuples_round_trip(
+            pair in any::<(u8, bool)>(),
+            six in any::<(i64, u16, u32, u64, bool, u8)>(),
+            text in any::<String>(),
+        ) {
+            round_trip(&pair)?;
+            round_trip(&six)?;
+            round_trip(&(text, pair))?;
+        }
+
+        #[test]
+        fn durations_round_trip(secs in any::<u64>(), nanos in 0..1_000_000_000u32) {
+            round_trip(&Duration::new(secs, nanos))?;
+        }
+
+        #[test]
+        fn chess_types_round_trip(chess_move in chess_move(), board in board()) {
+            round_trip(&chess_move)?;
+            round_trip(&board)?;
+        }
+
+        #[test]
+        fn compact_moves_take_two_bytes(chess_move in chess_move()) {
+            prop_assert_eq!(encode(&chess_move, Version::V2).len(), 2);
+        }
+
+        #[test]
+        fn trailing_bytes_are_rejected(val in any::<u64>(), extra in any::<u8>()) {
+            for version in VERSIONS {
+                let mut buf = encode(&val, version);
+                buf.push(extra);
+                prop_assert!(decode::<u64>(&buf, version).is_err());
+            }
+        }
+
+        #[test]
+        fn arbitrary_bytes_never_panic(bytes in collection::vec(any::<u8>(), 0..64)) {
+            for version in VERSIONS {
+                _ = decode::<Board>(&bytes, version);
+                _ = decode::<Move>(&bytes, version);
+                _ = decode::<HashMap<u8, Vec<Duration>>>(&bytes, version);
+            }
+        }
+    }
+
+    #[test]
+    fn overlong_varints_are_rejected() {
+        assert_eq!(decode::<u64>(&[0x7f], Version::V2).unwrap(), 0x7f);
+        assert!(decode::<u64>(&[0x80, 0x00], Version::V2).is_err());
+        assert!(decode::<u64>(&[0xff, 0x80, 0x00], Version::V2).is_err());
+    }
+
+    #[test]
+    fn varints_overflowing_u64_are_rejected() {
+        let mut max = [0xff; 10];
+        max[9] = 0x01;
+        assert_eq!(decode::<u64>(&max, Version::V2).unwrap(), u64::MAX);
+        max[9] = 0x02;
+        assert!(decode::<u64>(&max, Version::V2).is_err());
+        assert!(decode::<u64>(&[0xff; 11], Version::V2).is_err());
+        // fits in a u64 but not in the decoded type
+        let too_large = u64::from(u16::MAX) + 1;
+        assert!(decode::<u16>(&encode(&too_large, Version::V2), Version::V2).is_err());
+    }
+
+    #[test]
+    fn duplicate_map_keys_are_rejected() {
+        // a list of pairs has the same layout as a map
+        let pairs: Vec<(u8, u8)> = vec![(1, 2), (1, 3)];
+        for version in VERSIONS {
+            assert!(decode::<HashMap<u8, u8>>(&encode(&pairs, version), version).is_err());
+            let unique: Vec<(u8, u8)> = vec![(1, 2), (2, 3)];
+            let map: HashMap<u8, u8> = decode(&encode(&unique, version), version).unwrap();
+            assert_eq!(map, HashMap::from([(1, 2), (2, 3)]));
+        }
+    }
+
+    #[test]
+    fn invalid_durations_are_rejected() {
+        for version in VERSIONS {
+            let buf = encode(&(0u64, 1_000_000_000u32), version);
+            assert!(decode::<Duration>(&buf, version).is_err());
+        }
+    }
+
+    #[test]
+    fn collection_limits_are_enforced() {
+        let limits = Limits {
+            max_collection_len: 3,
+            ..Limits::default()
+        };
+        for version in VERSIONS {
+            let buf = encode(&vec![0u8; 4], version);
+            assert!(Vec::<u8>::decode_versioned(&buf[..], version, limits).is_err());
+            let buf = encode(&vec![0u8; 3], version);
+            assert!(Vec::<u8>::decode_versioned(&buf[..], version, limits).is_ok());
+        }
+    }
+}
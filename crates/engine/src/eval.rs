@@ -0,0 +1,144 @@
+//! Static evaluation based on material and piece-square tables.
+
+use cozy_chess::{Board, Color, Piece, Square};
+
+/// Material values in centipawns, indexed by [`Piece`]
+const PIECE_VALUES: [i32; Piece::NUM] = [100, 320, 330, 500, 900, 0];
+
+/// Non-pawn material of both sides at the start of the game, used to detect the endgame
+const OPENING_MATERIAL: i32 = 2 * (2 * 320 + 2 * 330 + 2 * 500 + 900);
+
+// Piece-square tables from white's point of view, written with the 8th rank first.
+
+#[rustfmt::skip]
+const PAWN: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const KNIGHT: [i32; 64] = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
+];
+
+#[rustfmt::skip]
+const BISHOP: [i32; 64] = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  5,  5, 10, 10,  5,  5,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10, 10, 10, 10, 10, 10, 10,-10,
+    -10,  5,  0,  0,  0,  0,  5,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const ROOK: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const QUEEN: [i32; 64] = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+     -5,  0,  5,  5,  5,  5,  0, -5,
+      0,  0,  5,  5,  5,  5,  0, -5,
+    -10,  5,  5,  5,  5,  5,  0,-10,
+    -10,  0,  5,  0,  0,  0,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const KING_MIDDLEGAME: [i32; 64] = [
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -20,-30,-30,-40,-40,-30,-30,-20,
+    -10,-20,-20,-20,-20,-20,-20,-10,
+     20, 20,  0,  0,  0,  0, 20, 20,
+     20, 30, 10,  0,  0, 10, 30, 20,
+];
+
+#[rustfmt::skip]
+const KING_ENDGAME: [i32; 64] = [
+    -50,-40,-30,-20,-20,-30,-40,-50,
+    -30,-20,-10,  0,  0,-10,-20,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-30,  0,  0,  0,  0,-30,-30,
+    -50,-30,-30,-30,-30,-30,-30,-50,
+];
+
+/// Returns the material value of `piece` in centipawns.
+pub fn piece_value(piece: Piece) -> i32 {
+    PIECE_VALUES[piece as usize]
+}
+
+fn table_index(color: Color, square: Square) -> usize {
+    match color {
+        Color::White => square.flip_rank() as usize,
+        Color::Black => square as usize,
+    }
+}
+
+/// Evaluates `board` in centipawns from the point of view of the side to move.
+pub fn evaluate(board: &Board) -> i32 {
+    let material: i32 = [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen]
+        .into_iter()
+        .map(|piece| board.pieces(piece).len() as i32 * piece_value(piece))
+        .sum();
+    // 256 in the opening, 0 once only kings and pawns are left
+    let phase = material.min(OPENING_MATERIAL) * 256 / OPENING_MATERIAL;
+
+    let mut score = 0;
+    for color in Color::ALL {
+        let mut side = 0;
+        for piece in Piece::ALL {
+            for square in board.colored_pieces(color, piece) {
+                let index = table_index(color, square);
+                side += piece_value(piece);
+                side += match piece {
+                    Piece::Pawn => PAWN[index],
+                    Piece::Knight => KNIGHT[index],
+                    Piece::Bishop => BISHOP[index],
+                    Piece::Rook => ROOK[index],
+                    Piece::Queen => QUEEN[index],
+                    Piece::King => {
+                        (KING_MIDDLEGAME[index] * phase + KING_ENDGAME[index] * (256 - phase)) / 256
+                    }
+                };
+            }
+        }
+        if color == board.side_to_move() {
+            score += side;
+        } else {
+            score -= side;
+        }
+    }
+    score
+}
@@ -0,0 +1,491 @@
+//! Small chess engine on top of cozy-chess, used for bots and offline play.
+//!
+//! It runs an iterative deepening alpha-beta search with a transposition table and a
+//! capture-only quiescence search. Searches are limited by depth and node count rather than
+//! wall time, so the same code works natively and in the browser.
+//!
+//! ```
+//! use engine::{Engine, Limits};
+//!
+//! let mut engine = Engine::new();
+//! let result = engine.search(&cozy_chess::Board::default(), Limits::level(3));
+//! println!("{:?}", result.unwrap().best_move);
+//! ```
+
+use cozy_chess::{Board, Move, Piece};
+use tt::{Bound, Entry, TranspositionTable};
+
+pub mod eval;
+mod tt;
+
+/// Score of delivering mate right now, mates further away score lower
+pub const MATE: i32 = 30_000;
+const INFINITY: i32 = 32_000;
+const MAX_PLY: usize = 64;
+const TT_SIZE: usize = 1 << 16;
+
+/// How much work a single search may do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum depth in plies
+    pub depth: u8,
+    /// Maximum number of visited nodes, the last completed iteration is used once exceeded
+    pub nodes: u64,
+    /// Random offset of up to this many centipawns added to evaluations, weakening play
+    pub noise: i32,
+}
+
+impl Limits {
+    pub const MIN_LEVEL: u8 = 1;
+    pub const MAX_LEVEL: u8 = 8;
+    pub const DEFAULT_LEVEL: u8 = 4;
+
+    /// Returns the limits of a strength level between [`Self::MIN_LEVEL`] and
+    /// [`Self::MAX_LEVEL`], levels outside that range are clamped.
+    pub fn level(level: u8) -> Self {
+        let (depth, nodes, noise) = match level.clamp(Self::MIN_LEVEL, Self::MAX_LEVEL) {
+            1 => (1, 1_000, 300),
+            2 => (2, 5_000, 200),
+            3 => (3, 10_000, 120),
+            4 => (4, 25_000, 60),
+            5 => (5, 60_000, 30),
+            6 => (6, 150_000, 10),
+            7 => (8, 400_000, 0),
+            _ => (MAX_PLY as u8, 1_000_000, 0),
+        };
+        Self {
+            depth,
+            nodes,
+            noise,
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self::level(Self::DEFAULT_LEVEL)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchResult {
+    pub best_move: Move,
+    /// Score in centipawns from the point of view of the side to move
+    pub score: i32,
+    /// Depth of the last completed iteration
+    pub depth: u8,
+    pub nodes: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Engine {
+    tt: TranspositionTable,
+    seed: u64,
+    limits: Limits,
+    nodes: u64,
+    aborted: bool,
+    /// Hashes of the positions leading to the current node, for repetition detection
+    path: Vec<u64>,
+    killers: [[Option<Move>; 2]; MAX_PLY],
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Self::with_seed(0)
+    }
+
+    /// Creates an engine whose evaluation noise depends on `seed`, so weak levels don't
+    /// always answer the same way.
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            tt: TranspositionTable::new(TT_SIZE),
+            seed,
+            limits: Limits::default(),
+            nodes: 0,
+            aborted: false,
+            path: Vec::with_capacity(MAX_PLY),
+            killers: [[None; 2]; MAX_PLY],
+        }
+    }
+
+    /// Forgets everything learned in previous searches.
+    pub fn new_game(&mut self) {
+        self.tt.clear();
+    }
+
+    /// Searches `board` and returns the best move found, or `None` if the game is over.
+    pub fn search(&mut self, board: &Board, limits: Limits) -> Option<SearchResult> {
+        let moves = legal_moves(board);
+        if moves.is_empty() {
+            return None;
+        }
+        if limits.noise != self.limits.noise {
+            // cached scores were computed with different noise
+            self.tt.clear();
+        }
+        self.limits = limits;
+        self.nodes = 0;
+        self.aborted = false;
+        self.killers = [[None; 2]; MAX_PLY];
+
+        let mut result = SearchResult {
+            best_move: moves[0],
+            score: 0,
+            depth: 0,
+            nodes: 0,
+        };
+        for depth in 1..=limits.depth.max(1) {
+            let Some((best_move, score)) = self.search_root(board, &moves, depth) else {
+                break;
+            };
+            result = SearchResult {
+                best_move,
+                score,
+                depth,
+                nodes: self.nodes,
+            };
+            if score.abs() >= MATE - MAX_PLY as i32 {
+                // a forced mate was found, searching deeper won't change anything
+                break;
+            }
+        }
+        result.nodes = self.nodes;
+        Some(result)
+    }
+
+    fn search_root(&mut self, board: &Board, moves: &[Move], depth: u8) -> Option<(Move, i32)> {
+        let hash = board.hash();
+        let mut moves = moves.to_vec();
+        let tt_move = self.tt.get(hash).and_then(|entry| entry.best_move);
+        self.order_moves(board, &mut moves, tt_move, 0);
+
+        let mut alpha = -INFINITY;
+        let mut best = None;
+        self.path.clear();
+        self.path.push(hash);
+        for chess_move in moves {
+            let mut child = board.clone();
+            child.play_unchecked(chess_move);
+            let score = -self.negamax(&child, depth - 1, -INFINITY, -alpha, 1);
+            if self.aborted {
+                return None;
+            }
+            if score > alpha {
+                alpha = score;
+                best = Some(chess_move);
+            }
+        }
+        self.path.pop();
+
+        self.tt.store(Entry {
+            hash,
+            depth,
+            score: alpha,
+            bound: Bound::Exact,
+            best_move: best,
+        });
+        best.map(|best| (best, alpha))
+    }
+
+    fn negamax(&mut self, board: &Board, depth: u8, mut alpha: i32, beta: i32, ply: usize) -> i32 {
+        if self.nodes >= self.limits.nodes {
+            self.aborted = true;
+        }
+        if self.aborted {
+            return 0;
+        }
+        let hash = board.hash();
+        if board.halfmove_clock() >= 100 || self.path.contains(&hash) {
+            return 0;
+        }
+        if depth == 0 || ply >= MAX_PLY {
+            return self.quiesce(board, alpha, beta, ply);
+        }
+        self.nodes += 1;
+
+        let entry = self.tt.get(hash);
+        if let Some(entry) = entry {
+            if entry.depth >= depth {
+                let score = score_from_tt(entry.score, ply);
+                match entry.bound {
+                    Bound::Exact => return score,
+                    Bound::Lower if score >= beta => return score,
+                    Bound::Upper if score <= alpha => return score,
+                    _ => {}
+                }
+            }
+        }
+
+        let mut moves = legal_moves(board);
+        if moves.is_empty() {
+            return if board.checkers().is_empty() {
+                0
+            } else {
+                -MATE + ply as i32
+            };
+        }
+        self.order_moves(
+            board,
+            &mut moves,
+            entry.and_then(|entry| entry.best_move),
+            ply,
+        );
+
+        let original_alpha = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+        self.path.push(hash);
+        for chess_move in moves {
+            let mut child = board.clone();
+            child.play_unchecked(chess_move);
+            let score = -self.negamax(&child, depth - 1, -beta, -alpha, ply + 1);
+            if self.aborted {
+                self.path.pop();
+                return 0;
+            }
+            if score > best_score {
+                best_score = score;
+                best_move = Some(chess_move);
+            }
+            if score > alpha {
+                alpha = score;
+            }
+            if alpha >= beta {
+                if !is_capture(board, chess_move) {
+                    let killers = &mut self.killers[ply];
+                    if killers[0] != Some(chess_move) {
+                        killers[1] = killers[0];
+                        killers[0] = Some(chess_move);
+                    }
+                }
+                break;
+            }
+        }
+        self.path.pop();
+
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.tt.store(Entry {
+            hash,
+            depth,
+            score: score_to_tt(best_score, ply),
+            bound,
+            best_move,
+        });
+        best_score
+    }
+
+    /// Searches captures only, so positions aren't evaluated in the middle of an exchange.
+    fn quiesce(&mut self, board: &Board, mut alpha: i32, beta: i32, ply: usize) -> i32 {
+        self.nodes += 1;
+        let stand_pat = self.evaluate(board);
+        if stand_pat >= beta || ply >= MAX_PLY {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+
+        let theirs = board.colors(!board.side_to_move());
+        let mut captures = Vec::new();
+        board.generate_moves(|mut piece_moves| {
+            piece_moves.to &= theirs;
+            captures.extend(piece_moves);
+            false
+        });
+        captures.sort_by_key(|&chess_move| -capture_score(board, chess_move));
+
+        for chess_move in captures {
+            let mut child = board.clone();
+            child.play_unchecked(chess_move);
+            let score = -self.quiesce(&child, -beta, -alpha, ply + 1);
+            if score >= beta {
+                return score;
+            }
+            alpha = alpha.max(score);
+        }
+        alpha
+    }
+
+    fn evaluate(&self, board: &Board) -> i32 {
+        let score = eval::evaluate(board);
+        if self.limits.noise > 0 {
+            // deterministic per position, so the transposition table stays consistent
+            let random = (board.hash() ^ self.seed).wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 33;
+            score + (random % (2 * self.limits.noise as u64 + 1)) as i32 - self.limits.noise
+        } else {
+            score
+        }
+    }
+
+    fn order_moves(&self, board: &Board, moves: &mut [Move], tt_move: Option<Move>, ply: usize) {
+        let killers = self.killers[ply.min(MAX_PLY - 1)];
+        moves.sort_by_cached_key(|&chess_move| {
+            let score = if Some(chess_move) == tt_move {
+                1_000_000
+            } else if is_capture(board, chess_move) {
+                100_000 + capture_score(board, chess_move)
+            } else if chess_move.promotion == Some(Piece::Queen) {
+                90_000
+            } else if killers.contains(&Some(chess_move)) {
+                80_000
+            } else {
+                0
+            };
+            -score
+        });
+    }
+}
+
+fn legal_moves(board: &Board) -> Vec<Move> {
+    let mut moves = Vec::new();
+    board.generate_moves(|piece_moves| {
+        moves.extend(piece_moves);
+        false
+    });
+    moves
+}
+
+fn is_capture(board: &Board, chess_move: Move) -> bool {
+    board.colors(!board.side_to_move()).has(chess_move.to)
+}
+
+/// Most valuable victim, least valuable attacker
+fn capture_score(board: &Board, chess_move: Move) -> i32 {
+    let victim = board.piece_on(chess_move.to).map_or(0, eval::piece_value);
+    let attacker = board.piece_on(chess_move.from).map_or(0, eval::piece_value);
+    victim * 10 - attacker / 10
+}
+
+/// Mate scores are stored relative to the node instead of the root.
+fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE - MAX_PLY as i32 {
+        score + ply as i32
+    } else if score <= -MATE + MAX_PLY as i32 {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE - MAX_PLY as i32 {
+        score - ply as i32
+    } else if score <= -MATE + MAX_PLY as i32 {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(fen: &str) -> Board {
+        fen.parse().unwrap()
+    }
+
+    fn exact(depth: u8) -> Limits {
+        Limits {
+            depth,
+            nodes: 1_000_000,
+            noise: 0,
+        }
+    }
+
+    #[test]
+    fn finds_mate_in_one() {
+        let back_rank = board("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1");
+        let result = Engine::new().search(&back_rank, exact(3)).unwrap();
+        assert_eq!(result.best_move, "a1a8".parse().unwrap());
+        assert_eq!(result.score, MATE - 1);
+    }
+
+    #[test]
+    fn does_not_hang_a_piece() {
+        // the knight on e4 is attacked by the pawn on d5, white is ahead as long as it survives
+        let attacked = board("4k3/pp6/8/3p4/4N3/8/PP6/4K3 w - - 0 1");
+        let mut engine = Engine::new();
+        for depth in 1..=3 {
+            let result = engine.search(&attacked, exact(depth)).unwrap();
+            let mut reply = attacked.clone();
+            reply.play(result.best_move);
+            let punished = engine.search(&reply, exact(2)).unwrap();
+            assert!(
+                punished.score < 0,
+                "depth {depth} played {} allowing {}",
+                result.best_move,
+                punished.best_move
+            );
+        }
+    }
+
+    #[test]
+    fn no_result_when_the_game_is_over() {
+        let mut engine = Engine::new();
+        let checkmate = board("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3");
+        assert_eq!(engine.search(&checkmate, Limits::default()), None);
+        let stalemate = board("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+        assert_eq!(engine.search(&stalemate, Limits::default()), None);
+    }
+
+    #[test]
+    fn levels_are_clamped() {
+        assert_eq!(Limits::level(0), Limits::level(Limits::MIN_LEVEL));
+        assert_eq!(Limits::level(200), Limits::level(Limits::MAX_LEVEL));
+        for level in Limits::MIN_LEVEL..Limits::MAX_LEVEL {
+            let (weaker, stronger) = (Limits::level(level), Limits::level(level + 1));
+            assert!(weaker.depth <= stronger.depth && weaker.nodes <= stronger.nodes);
+            assert!(weaker.noise >= stronger.noise);
+        }
+    }
+
+    #[test]
+    fn stops_at_the_node_limit() {
+        let limits = Limits {
+            depth: MAX_PLY as u8,
+            nodes: 2_000,
+            noise: 0,
+        };
+        let result = Engine::new().search(&Board::default(), limits).unwrap();
+        assert!(result.depth < limits.depth);
+        // the running quiescence search finishes its capture sequence
+        assert!(result.nodes <= limits.nodes + 100, "{} nodes", result.nodes);
+    }
+
+    #[test]
+    fn falls_back_to_a_legal_move_without_completed_iterations() {
+        let limits = Limits {
+            depth: 4,
+            nodes: 0,
+            noise: 0,
+        };
+        let start = Board::default();
+        let result = Engine::new().search(&start, limits).unwrap();
+        assert_eq!(result.depth, 0);
+        assert!(start.is_legal(result.best_move));
+    }
+
+    #[test]
+    fn mate_scores_round_trip_through_the_table() {
+        for ply in [0, 1, 7, MAX_PLY - 1] {
+            for score in [MATE - 1, MATE - 20, -MATE + 3, -MATE + 30, 0, 250, -900] {
+                assert_eq!(score_from_tt(score_to_tt(score, ply), ply), score);
+            }
+        }
+        // a mate 3 plies below a node at ply 5 is stored as mate in 3 from that node
+        assert_eq!(score_to_tt(MATE - 8, 5), MATE - 3);
+        assert_eq!(score_from_tt(MATE - 3, 2), MATE - 5);
+        assert_eq!(score_to_tt(-MATE + 8, 5), -MATE + 3);
+    }
+}
@@ -0,0 +1,56 @@
+//! Transposition table caching search results by position hash.
+
+use cozy_chess::Move;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    /// The score is at least this high (fail-high)
+    Lower,
+    /// The score is at most this high (fail-low)
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    pub hash: u64,
+    pub depth: u8,
+    pub score: i32,
+    pub bound: Bound,
+    pub best_move: Option<Move>,
+}
+
+/// Fixed size, always-replace-if-deeper hash table
+#[derive(Debug, Clone)]
+pub struct TranspositionTable {
+    entries: Vec<Option<Entry>>,
+}
+
+impl TranspositionTable {
+    /// Creates a table with `size` entries, rounded up to a power of two.
+    pub fn new(size: usize) -> Self {
+        Self {
+            entries: vec![None; size.next_power_of_two()],
+        }
+    }
+
+    fn index(&self, hash: u64) -> usize {
+        hash as usize & (self.entries.len() - 1)
+    }
+
+    pub fn get(&self, hash: u64) -> Option<Entry> {
+        self.entries[self.index(hash)].filter(|entry| entry.hash == hash)
+    }
+
+    pub fn store(&mut self, entry: Entry) {
+        let index = self.index(entry.hash);
+        let slot = &mut self.entries[index];
+        if slot.is_none_or(|old| old.hash != entry.hash || old.depth <= entry.depth) {
+            *slot = Some(entry);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.fill(None);
+    }
+}
@@ -0,0 +1,205 @@
+//! Sounds, the tab title and browser notifications, so players notice moves in a background tab.
+//!
+//! Sounds are synthesized with the Web Audio API. Whether they are muted and whether
+//! notifications are enabled is remembered in `localStorage`.
+
+use std::cell::RefCell;
+
+use cozy_chess::{Board, GameStatus, Move, Piece};
+use log::warn;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{AudioContext, Notification, NotificationOptions, NotificationPermission};
+
+use crate::appearance::storage;
+
+const MUTED_KEY: &str = "muted";
+const NOTIFICATIONS_KEY: &str = "notifications";
+
+/// Green dot shown as the favicon while it's the player's turn
+const TURN_FAVICON: &str = "data:image/svg+xml,<svg xmlns='http://www.w3.org/2000/svg' viewBox='0 0 16 16'><circle cx='8' cy='8' r='7' fill='%2322a34a'/></svg>";
+const FAVICON_ID: &str = "turn-favicon";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Sound {
+    Move,
+    Capture,
+    Castle,
+    Check,
+    GameEnd,
+}
+
+impl Sound {
+    /// Sound of `chess_move` played in `board`, moves that end the game or give check take
+    /// precedence over captures and castling
+    pub fn of(board: &Board, chess_move: Move) -> Self {
+        let mut after = board.clone();
+        after.play_unchecked(chess_move);
+        let mover = board.color_on(chess_move.from);
+        let pawn = board.piece_on(chess_move.from) == Some(Piece::Pawn);
+        if after.status() != GameStatus::Ongoing {
+            Self::GameEnd
+        } else if !after.checkers().is_empty() {
+            Self::Check
+        } else if board.color_on(chess_move.to) == mover {
+            // castling is written as the king capturing its own rook
+            Self::Castle
+        } else if board.occupied().has(chess_move.to)
+            || (pawn && chess_move.from.file() != chess_move.to.file())
+        {
+            Self::Capture
+        } else {
+            Self::Move
+        }
+    }
+
+    /// Frequency in Hz, start and duration in seconds of each tone
+    fn tones(self) -> &'static [(f32, f64, f64)] {
+        match self {
+            Self::Move => &[(440.0, 0.0, 0.08)],
+            Self::Capture => &[(330.0, 0.0, 0.06), (220.0, 0.05, 0.1)],
+            Self::Castle => &[(440.0, 0.0, 0.07), (440.0, 0.1, 0.07)],
+            Self::Check => &[(660.0, 0.0, 0.08), (880.0, 0.08, 0.12)],
+            Self::GameEnd => &[(523.0, 0.0, 0.15), (659.0, 0.15, 0.15), (784.0, 0.3, 0.3)],
+        }
+    }
+}
+
+thread_local! {
+    /// Created on the first sound, browsers limit the number of audio contexts
+    static AUDIO: RefCell<Option<AudioContext>> = const { RefCell::new(None) };
+    /// Title of the page before it was changed to show the player's turn
+    static TITLE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Plays `sound` unless sounds are muted
+pub fn play(sound: Sound) {
+    if muted() {
+        return;
+    }
+    AUDIO.with(|audio| {
+        let mut audio = audio.borrow_mut();
+        if audio.is_none() {
+            *audio = AudioContext::new()
+                .map_err(|err| warn!("failed to create audio context: {err:?}"))
+                .ok();
+        }
+        let Some(audio) = audio.as_ref() else {
+            return;
+        };
+        // contexts created before the user interacted with the page start suspended
+        _ = audio.resume();
+        let now = audio.current_time();
+        for &(frequency, start, duration) in sound.tones() {
+            if let Err(err) = play_tone(audio, frequency, now + start, duration) {
+                warn!("failed to play sound: {err:?}");
+                return;
+            }
+        }
+    });
+}
+
+fn play_tone(
+    audio: &AudioContext,
+    frequency: f32,
+    start: f64,
+    duration: f64,
+) -> Result<(), js_sys::wasm_bindgen::JsValue> {
+    let oscillator = audio.create_oscillator()?;
+    let gain = audio.create_gain()?;
+    oscillator.frequency().set_value(frequency);
+    gain.gain().set_value_at_time(0.2, start)?;
+    gain.gain()
+        .exponential_ramp_to_value_at_time(0.001, start + duration)?;
+    oscillator.connect_with_audio_node(&gain)?;
+    gain.connect_with_audio_node(&audio.destination())?;
+    oscillator.start_with_when(start)?;
+    oscillator.stop_with_when(start + duration)
+}
+
+fn get_flag(key: &str) -> bool {
+    storage()
+        .and_then(|storage| storage.get_item(key).ok().flatten())
+        .is_some_and(|value| value == "true")
+}
+
+fn set_flag(key: &str, value: bool) {
+    let Some(storage) = storage() else {
+        warn!("local storage is not available, {key} won't be remembered");
+        return;
+    };
+    if let Err(err) = storage.set_item(key, &value.to_string()) {
+        warn!("failed to store {key}: {err:?}");
+    }
+}
+
+pub fn muted() -> bool {
+    get_flag(MUTED_KEY)
+}
+
+pub fn set_muted(muted: bool) {
+    set_flag(MUTED_KEY, muted);
+}
+
+/// Whether notifications are enabled and allowed by the browser
+pub fn notifications() -> bool {
+    get_flag(NOTIFICATIONS_KEY) && Notification::permission() == NotificationPermission::Granted
+}
+
+/// Enables notifications, asking the browser for permission if necessary
+pub async fn set_notifications(enabled: bool) {
+    set_flag(NOTIFICATIONS_KEY, enabled);
+    if enabled && Notification::permission() == NotificationPermission::Default {
+        match Notification::request_permission() {
+            Ok(permission) => _ = JsFuture::from(permission).await,
+            Err(err) => warn!("failed to request notification permission: {err:?}"),
+        }
+    }
+}
+
+/// Shows a notification if they're enabled and the page is in the background
+pub fn notify(title: &str, body: &str) {
+    let hidden = web_sys::window()
+        .and_then(|window| window.document())
+        .is_some_and(|document| document.hidden());
+    if !hidden || !notifications() {
+        return;
+    }
+    let mut options = NotificationOptions::new();
+    options.body(body);
+    if let Err(err) = Notification::new_with_options(title, &options) {
+        warn!("failed to show notification: {err:?}");
+    }
+}
+
+/// Marks the tab title and favicon while it's the player's turn
+pub fn show_turn(your_turn: bool) {
+    let Some(document) = web_sys::window().and_then(|window| window.document()) else {
+        return;
+    };
+    let title = TITLE.with(|title| {
+        title
+            .borrow_mut()
+            .get_or_insert_with(|| document.title())
+            .clone()
+    });
+    let favicon = document.get_element_by_id(FAVICON_ID);
+    if your_turn {
+        document.set_title(&format!("Your turn - {title}"));
+        if favicon.is_none() {
+            let Ok(link) = document.create_element("link") else {
+                return;
+            };
+            _ = link.set_attribute("id", FAVICON_ID);
+            _ = link.set_attribute("rel", "icon");
+            _ = link.set_attribute("href", TURN_FAVICON);
+            if let Some(head) = document.head() {
+                _ = head.append_child(&link);
+            }
+        }
+    } else {
+        document.set_title(&title);
+        if let Some(favicon) = favicon {
+            favicon.remove();
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! Piece sets and board colors, remembered in `localStorage`.
+//!
+//! The piece sets are SVG files in `assets/pieces/<set>`, which the backend serves.
+
+use cozy_chess::{Color, Piece};
+use log::warn;
+
+const PIECE_SET_KEY: &str = "piece_set";
+const BOARD_THEME_KEY: &str = "board_theme";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum PieceSet {
+    #[default]
+    Geometric,
+    Letters,
+}
+
+impl PieceSet {
+    pub const ALL: [Self; 2] = [Self::Geometric, Self::Letters];
+
+    /// Directory of the set, also used as the stored value
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::Geometric => "geometric",
+            Self::Letters => "letters",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Geometric => "Geometric",
+            Self::Letters => "Letters",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|set| set.id() == id)
+    }
+
+    pub fn url(self, color: Color, piece: Piece) -> String {
+        let color = match color {
+            Color::White => 'w',
+            Color::Black => 'b',
+        };
+        let piece = char::from(piece).to_ascii_uppercase();
+        format!("/pieces/{}/{color}{piece}.svg", self.id())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum BoardTheme {
+    #[default]
+    Brown,
+    Green,
+    Blue,
+    Classic,
+}
+
+impl BoardTheme {
+    pub const ALL: [Self; 4] = [Self::Brown, Self::Green, Self::Blue, Self::Classic];
+
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::Brown => "brown",
+            Self::Green => "green",
+            Self::Blue => "blue",
+            Self::Classic => "classic",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Brown => "Brown",
+            Self::Green => "Green",
+            Self::Blue => "Blue",
+            Self::Classic => "Black & White",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|theme| theme.id() == id)
+    }
+
+    /// Colors of the light and the dark squares
+    pub fn colors(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Brown => ("#f0d9b5", "#b58863"),
+            Self::Green => ("#eeeed2", "#769656"),
+            Self::Blue => ("#dee3e6", "#8ca2ad"),
+            Self::Classic => ("white", "black"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Appearance {
+    pub piece_set: PieceSet,
+    pub board_theme: BoardTheme,
+}
+
+pub(crate) fn storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok().flatten()
+}
+
+impl Appearance {
+    /// The stored appearance, with defaults for anything that wasn't chosen yet
+    pub fn load() -> Self {
+        let Some(storage) = storage() else {
+            return Self::default();
+        };
+        let get = |key| storage.get_item(key).ok().flatten();
+        Self {
+            piece_set: get(PIECE_SET_KEY)
+                .and_then(|id| PieceSet::from_id(&id))
+                .unwrap_or_default(),
+            board_theme: get(BOARD_THEME_KEY)
+                .and_then(|id| BoardTheme::from_id(&id))
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn save(&self) {
+        let Some(storage) = storage() else {
+            warn!("local storage is not available, appearance won't be remembered");
+            return;
+        };
+        for (key, value) in [
+            (PIECE_SET_KEY, self.piece_set.id()),
+            (BOARD_THEME_KEY, self.board_theme.id()),
+        ] {
+            if let Err(err) = storage.set_item(key, value) {
+                warn!("failed to store {key}: {err:?}");
+            }
+        }
+    }
+}
@@ -1,6 +1,13 @@
+pub mod account;
+pub mod analysis;
 pub mod board;
+pub mod editor;
 pub mod host;
 pub mod ingame;
 pub mod join;
+pub mod leaderboard;
+pub mod lobby_list;
 pub mod menu;
+pub mod offline;
+pub mod seek;
 pub mod waiting_for_opponent;
@@ -0,0 +1,135 @@
+use common::http::{LoginRequest, RegisterRequest};
+use reqwasm::http::Request;
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+use yew_router::prelude::*;
+
+use crate::Route;
+
+/// Login and registration form, the session cookie is set by the server
+#[derive(Debug, Default)]
+pub struct Account {
+    username_ref: NodeRef,
+    password_ref: NodeRef,
+    display_name_ref: NodeRef,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Properties)]
+pub struct Props {
+    /// Create a new account instead of logging in
+    #[prop_or_default]
+    pub register: bool,
+}
+
+pub enum Msg {
+    Submit,
+    Error(String),
+    LoggedIn,
+}
+
+impl Component for Account {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self::default()
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let register = ctx.props().register;
+        let submit = ctx.link().callback(|e: SubmitEvent| {
+            e.prevent_default();
+            Msg::Submit
+        });
+        let (title, other, other_route) = if register {
+            ("Register", "Log in instead", Route::Login)
+        } else {
+            ("Log in", "Create an account", Route::Register)
+        };
+
+        html! {
+            <div>
+                <h3>{title}</h3>
+                <form onsubmit={submit}>
+                    <input placeholder={"Username"} autocomplete={"username"} ref={&self.username_ref}/>
+                    <input
+                        type={"password"}
+                        placeholder={"Password"}
+                        autocomplete={if register { "new-password" } else { "current-password" }}
+                        ref={&self.password_ref}
+                    />
+                    if register {
+                        <input placeholder={"Display name (optional)"} ref={&self.display_name_ref}/>
+                    }
+                    <button type={"submit"}>{title}</button>
+                </form>
+                if let Some(err) = &self.error {
+                    <p>{err}</p>
+                }
+                <Link<Route> to={other_route}>{other}</Link<Route>>
+                {" or "}
+                <Link<Route> to={Route::Home}>{"play as a guest"}</Link<Route>>
+            </div>
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Msg::Submit => {
+                let value = |node: &NodeRef| {
+                    node.cast::<HtmlInputElement>()
+                        .map(|input| input.value())
+                        .unwrap_or_default()
+                };
+                let username = value(&self.username_ref);
+                let password = value(&self.password_ref);
+                let (url, body) = if ctx.props().register {
+                    let display_name = value(&self.display_name_ref);
+                    let request = RegisterRequest {
+                        username,
+                        password,
+                        display_name: (!display_name.trim().is_empty()).then_some(display_name),
+                    };
+                    ("/api/account/register", serde_json::to_string(&request))
+                } else {
+                    let request = LoginRequest { username, password };
+                    ("/api/account/login", serde_json::to_string(&request))
+                };
+                ctx.link().send_future(async move {
+                    let response = match Request::post(url)
+                        .header("Content-Type", "application/json")
+                        .body(body.unwrap())
+                        .send()
+                        .await
+                    {
+                        Ok(response) => response,
+                        Err(err) => return Msg::Error(err.to_string()),
+                    };
+                    match response.status() {
+                        200 => Msg::LoggedIn,
+                        400 => Msg::Error(
+                            "Usernames may only contain letters, digits, '-' and '_', passwords need at least 8 characters"
+                                .to_string(),
+                        ),
+                        401 => Msg::Error("Wrong username or password".to_string()),
+                        409 => Msg::Error("This username is already taken".to_string()),
+                        other => Msg::Error(format!(
+                            "Unhandled status code {other} ({})",
+                            response.status_text(),
+                        )),
+                    }
+                });
+                false
+            }
+            Msg::Error(err) => {
+                self.error = Some(err);
+                true
+            }
+            Msg::LoggedIn => {
+                ctx.link().navigator().unwrap().push(&Route::Home);
+                false
+            }
+        }
+    }
+}
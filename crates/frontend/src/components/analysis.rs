@@ -0,0 +1,318 @@
+use common::{http::GameRecord, notation};
+use cozy_chess::{Board as ChessBoard, Color, Move};
+use reqwasm::http::Request;
+use uuid::Uuid;
+use web_sys::{HtmlElement, HtmlInputElement};
+use yew::prelude::*;
+
+use super::board::{Board, LAYOUT, SIDE_PANEL};
+
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    /// Game whose moves are loaded as the main line
+    #[prop_or_default]
+    pub game: Option<Uuid>,
+}
+
+/// A position in the move tree, reached by playing `chess_move` in the parent position
+#[derive(Debug, Clone)]
+struct Node {
+    chess_move: Option<Move>,
+    san: String,
+    /// Plies since the start position
+    ply: usize,
+    parent: Option<usize>,
+    /// The first child continues the line, the others are variations
+    children: Vec<usize>,
+}
+
+/// Board where both sides can move, every move branches off a tree of variations
+pub struct Analysis {
+    start: ChessBoard,
+    /// Node 0 is the start position
+    nodes: Vec<Node>,
+    current: usize,
+    /// White and black player of the loaded game
+    players: Option<(String, String)>,
+    container_ref: NodeRef,
+    fen_ref: NodeRef,
+    error: Option<String>,
+}
+
+pub enum Msg {
+    PlayMove(Move),
+    Select(usize),
+    Key(KeyboardEvent),
+    LoadFen,
+    Loaded(GameRecord),
+    Error(String),
+}
+
+const ROOT: usize = 0;
+
+impl Analysis {
+    fn reset(&mut self, start: ChessBoard) {
+        self.start = start;
+        self.nodes = vec![Node {
+            chess_move: None,
+            san: String::new(),
+            ply: 0,
+            parent: None,
+            children: Vec::new(),
+        }];
+        self.current = ROOT;
+    }
+
+    fn board_at(&self, node: usize) -> ChessBoard {
+        let mut moves = Vec::new();
+        let mut next = Some(node);
+        while let Some(node) = next {
+            moves.extend(self.nodes[node].chess_move);
+            next = self.nodes[node].parent;
+        }
+        let mut board = self.start.clone();
+        for &chess_move in moves.iter().rev() {
+            board.play_unchecked(chess_move);
+        }
+        board
+    }
+
+    /// Plays a legal move after `parent`, reusing the node if the move was played before.
+    fn add_move(&mut self, parent: usize, board: &ChessBoard, chess_move: Move) -> usize {
+        if let Some(&existing) = self.nodes[parent]
+            .children
+            .iter()
+            .find(|&&child| self.nodes[child].chess_move == Some(chess_move))
+        {
+            return existing;
+        }
+        let node = self.nodes.len();
+        self.nodes.push(Node {
+            chess_move: Some(chess_move),
+            san: notation::to_san(board, chess_move),
+            ply: self.nodes[parent].ply + 1,
+            parent: Some(parent),
+            children: Vec::new(),
+        });
+        self.nodes[parent].children.push(node);
+        node
+    }
+
+    /// Moves following `node` with their variations in parentheses, `numbered` forces a move
+    /// number in front of the first move
+    fn line(&self, ctx: &Context<Self>, mut node: usize, mut numbered: bool, out: &mut Vec<Html>) {
+        loop {
+            let Some((&main, variations)) = self.nodes[node].children.split_first() else {
+                return;
+            };
+            out.push(self.move_button(ctx, main, numbered));
+            for &variation in variations {
+                out.push(html! { {"( "} });
+                out.push(self.move_button(ctx, variation, true));
+                self.line(ctx, variation, false, out);
+                out.push(html! { {") "} });
+            }
+            numbered = !variations.is_empty();
+            node = main;
+        }
+    }
+
+    fn move_button(&self, ctx: &Context<Self>, node: usize, numbered: bool) -> Html {
+        let Node { san, ply, .. } = &self.nodes[node];
+        // the start position might have black to move
+        let ply = ply - 1 + usize::from(self.start.side_to_move() == Color::Black);
+        let number = usize::from(self.start.fullmove_number()) + ply / 2;
+        let label = if ply % 2 == 0 {
+            format!("{number}. {san}")
+        } else if numbered {
+            format!("{number}... {san}")
+        } else {
+            san.clone()
+        };
+        let onclick = ctx.link().callback(move |_| Msg::Select(node));
+        let style = if node == self.current {
+            "font-weight: bold;"
+        } else {
+            ""
+        };
+        html! {
+            <>
+                <a {style} {onclick}>{label}</a>
+                {" "}
+            </>
+        }
+    }
+}
+
+impl Component for Analysis {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        let mut analysis = Self {
+            start: ChessBoard::default(),
+            nodes: Vec::new(),
+            current: ROOT,
+            players: None,
+            container_ref: NodeRef::default(),
+            fen_ref: NodeRef::default(),
+            error: None,
+        };
+        analysis.reset(ChessBoard::default());
+        if let Some(id) = ctx.props().game {
+            ctx.link().send_future(async move {
+                let response = match Request::get(&format!("/api/game/{id}")).send().await {
+                    Ok(response) if response.status() == 404 => {
+                        return Msg::Error("The game doesn't exist (anymore)".to_string())
+                    }
+                    Ok(response) => response,
+                    Err(err) => return Msg::Error(err.to_string()),
+                };
+                match response.json().await {
+                    Ok(record) => Msg::Loaded(record),
+                    Err(err) => Msg::Error(err.to_string()),
+                }
+            });
+        }
+        analysis
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let board = self.board_at(self.current);
+        let fen = board.to_string();
+        let last_move = self.nodes[self.current].chess_move;
+        let play_move = ctx.link().callback(Msg::PlayMove);
+        let onkeydown = ctx.link().callback(Msg::Key);
+        let load_fen = ctx.link().callback(|e: SubmitEvent| {
+            e.prevent_default();
+            Msg::LoadFen
+        });
+
+        let mut moves = Vec::new();
+        self.line(ctx, ROOT, true, &mut moves);
+
+        html! {
+            <div ref={&self.container_ref} tabindex="0" {onkeydown} style="outline: none;">
+                <h3>{"Analysis board"}</h3>
+                if let Some((white, black)) = &self.players {
+                    <p>{format!("{white} vs. {black}")}</p>
+                }
+                <div style={LAYOUT}>
+                    <div>
+                        <Board {board} color={Color::White} {last_move} {play_move} free=true/>
+                    </div>
+                    <div style={SIDE_PANEL}>
+                        <p>{"Use the arrow keys to go through the moves, click a move to jump to it."}</p>
+                        <p>{moves}</p>
+                        <p><code style="word-break: break-all;">{fen}</code></p>
+                        <form onsubmit={load_fen}>
+                            <input placeholder={"FEN"} ref={&self.fen_ref} size="60"/>
+                            <button type={"submit"}>{"Set up position"}</button>
+                        </form>
+                        if let Some(err) = &self.error {
+                            <p>{err}</p>
+                        }
+                    </div>
+                </div>
+            </div>
+        }
+    }
+
+    fn rendered(&mut self, _ctx: &Context<Self>, first_render: bool) {
+        // arrow keys only reach the container while it's focused
+        if first_render {
+            if let Some(container) = self.container_ref.cast::<HtmlElement>() {
+                _ = container.focus();
+            }
+        }
+    }
+
+    fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Msg::PlayMove(chess_move) => {
+                let board = self.board_at(self.current);
+                if !board.is_legal(chess_move) {
+                    return false;
+                }
+                self.current = self.add_move(self.current, &board, chess_move);
+                true
+            }
+            Msg::Select(node) => {
+                self.current = node;
+                true
+            }
+            Msg::Key(e) => {
+                // typing a move or a FEN
+                if e.target_dyn_into::<HtmlInputElement>().is_some() {
+                    return false;
+                }
+                let node = &self.nodes[self.current];
+                let next = match e.key().as_str() {
+                    "ArrowLeft" => node.parent,
+                    "ArrowRight" => node.children.first().copied(),
+                    "ArrowUp" | "Home" => Some(ROOT),
+                    "ArrowDown" | "End" => {
+                        let mut node = self.current;
+                        while let Some(&child) = self.nodes[node].children.first() {
+                            node = child;
+                        }
+                        Some(node)
+                    }
+                    _ => return false,
+                };
+                e.prevent_default();
+                match next {
+                    Some(next) if next != self.current => {
+                        self.current = next;
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            Msg::LoadFen => {
+                let Some(input) = self.fen_ref.cast::<HtmlInputElement>() else {
+                    return false;
+                };
+                match input.value().trim().parse() {
+                    Ok(board) => {
+                        self.reset(board);
+                        self.error = None;
+                    }
+                    Err(err) => self.error = Some(format!("Invalid FEN: {err}")),
+                }
+                true
+            }
+            Msg::Loaded(record) => {
+                let board = match record.fen.parse::<ChessBoard>() {
+                    Ok(board) => board,
+                    Err(err) => {
+                        self.error =
+                            Some(format!("The game started from an invalid position: {err}"));
+                        return true;
+                    }
+                };
+                self.reset(board.clone());
+                let mut board = board;
+                let mut node = ROOT;
+                for uci in &record.moves {
+                    let chess_move = match notation::parse_uci(&board, uci) {
+                        Ok(chess_move) => chess_move,
+                        Err(err) => {
+                            self.error = Some(format!("The game contains an invalid move: {err}"));
+                            break;
+                        }
+                    };
+                    node = self.add_move(node, &board, chess_move);
+                    board.play_unchecked(chess_move);
+                }
+                self.current = node;
+                self.players = Some((record.white, record.black));
+                true
+            }
+            Msg::Error(err) => {
+                self.error = Some(err);
+                true
+            }
+        }
+    }
+}
@@ -1,23 +1,279 @@
-use cozy_chess::{BitBoard, Board as ChessBoard, Color, File, Move, Piece, Rank, Square};
+use common::notation;
+use cozy_chess::{
+    get_bishop_rays, get_king_moves, get_knight_moves, get_pawn_attacks, get_pawn_quiets,
+    get_rook_rays, BitBoard, Board as ChessBoard, Color, File, Move, Piece, Rank, Square,
+};
 use log::info;
-use web_sys::HtmlElement;
+use web_sys::{Element, HtmlElement, HtmlInputElement, HtmlSelectElement};
 use yew::prelude::*;
 
+use crate::{
+    alerts::{self, Sound},
+    appearance::{Appearance, BoardTheme, PieceSet},
+};
+
 #[derive(Properties, PartialEq)]
 pub struct Props {
     pub board: ChessBoard,
     pub color: Color,
+    /// Highlighted along with the current position
+    #[prop_or_default]
+    pub last_move: Option<Move>,
+    /// Ignore input, e.g. once the game is over
+    #[prop_or_default]
+    pub disabled: bool,
+    /// Let both sides move, `color` only orients the board
+    #[prop_or_default]
+    pub free: bool,
     pub play_move: Callback<Move>,
 }
 
 #[derive(Debug, Clone, Default, PartialEq)]
 pub struct Board {
     selected_square: Option<Square>,
+    /// The board element, events are delegated by yew so their current target can't be used
+    board_ref: NodeRef,
+    /// Piece following the pointer
+    drag: Option<Drag>,
+    /// Move entered during the opponent's turn, played once they moved if it's still legal
+    premove: Option<Move>,
+    /// Pawn move to the last rank waiting for the player to pick a piece
+    promotion: Option<Move>,
+    /// Show the board from the opponent's side
+    flipped: bool,
+    appearance: Appearance,
+    /// Square focused for keyboard navigation, the bottom left corner until the arrow keys move it
+    cursor: Option<Square>,
+    /// Move the focus to the cursor after rendering
+    focus_cursor: bool,
+    cursor_ref: NodeRef,
+    move_input_ref: NodeRef,
+    /// Why the typed move couldn't be played
+    move_error: Option<String>,
+    /// Read by screen readers whenever it changes, e.g. the opponent's last move
+    announcement: String,
+}
+
+/// Position on the board in percent, measured from the top left corner
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Position {
+    x: f64,
+    y: f64,
+}
+
+impl Position {
+    /// Position of the pointer relative to the board
+    fn of(e: &PointerEvent, board: &NodeRef) -> Option<Self> {
+        let rect = board.cast::<Element>()?.get_bounding_client_rect();
+        Some(Self {
+            x: (f64::from(e.client_x()) - rect.left()) / rect.width() * 100.0,
+            y: (f64::from(e.client_y()) - rect.top()) / rect.height() * 100.0,
+        })
+    }
+
+    /// Square below the position when `orientation` is at the bottom of the board
+    fn square(self, orientation: Color) -> Option<Square> {
+        let column = (self.x / 12.5).floor();
+        let row = (self.y / 12.5).floor();
+        if !(0.0..8.0).contains(&column) || !(0.0..8.0).contains(&row) {
+            return None;
+        }
+        let (column, row) = (column as usize, row as usize);
+        Some(match orientation {
+            Color::White => Square::new(File::index(column), Rank::index(7 - row)),
+            Color::Black => Square::new(File::index(7 - column), Rank::index(row)),
+        })
+    }
+}
+
+/// Edge length of the board, fills phones while leaving room for the controls
+pub(super) const BOARD_SIZE: &str = "min(96vw, 80vh, 800px)";
+/// Container putting a panel next to the board, or below it when the screen is too narrow
+pub(super) const LAYOUT: &str =
+    "display: flex; flex-wrap: wrap; gap: 1em; align-items: flex-start;";
+pub(super) const SIDE_PANEL: &str = "flex: 1 1 18em; max-width: 40em;";
+
+/// Offset of a square from the left and the bottom of the board in percent
+fn offset(square: Square, orientation: Color) -> (f32, f32) {
+    let (file, rank) = (square.file() as usize, square.rank() as usize);
+    let (column, row) = match orientation {
+        Color::White => (file, rank),
+        Color::Black => (7 - file, 7 - rank),
+    };
+    (12.5 * column as f32, 12.5 * row as f32)
+}
+
+pub(super) fn is_dark(square: Square) -> bool {
+    (square.file() as usize + square.rank() as usize).is_multiple_of(2)
+}
+
+/// CSS placing an element on `square`
+pub(super) fn placement(square: Square, orientation: Color) -> String {
+    let (left, bottom) = offset(square, orientation);
+    format!("left: {left}%; bottom: {bottom}%;")
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Drag {
+    from: Square,
+    position: Position,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Msg {
-    ClickSquare(Square),
+    PointerDown(Position),
+    PointerMove(Position),
+    PointerUp(Position),
+    PointerCancel,
+    CancelPremove,
+    Flip,
+    SetPieceSet(PieceSet),
+    SetBoardTheme(BoardTheme),
+    ToggleSound,
+    /// Moves the keyboard focus by the given number of columns and rows as seen on screen
+    MoveCursor {
+        right: i8,
+        up: i8,
+    },
+    /// Clicks the square with the keyboard focus
+    Activate,
+    SubmitMove,
+}
+
+/// Squares the piece of `color` on `square` might be able to move to after the opponent's
+/// move, ignoring blockers since they might move away.
+fn premove_destinations(board: &ChessBoard, color: Color, square: Square) -> BitBoard {
+    if board.color_on(square) != Some(color) {
+        return BitBoard::EMPTY;
+    }
+    match board.piece_on(square) {
+        Some(Piece::Pawn) => {
+            get_pawn_quiets(square, color, BitBoard::EMPTY) | get_pawn_attacks(square, color)
+        }
+        Some(Piece::Knight) => get_knight_moves(square),
+        Some(Piece::Bishop) => get_bishop_rays(square),
+        Some(Piece::Rook) => get_rook_rays(square),
+        Some(Piece::Queen) => get_bishop_rays(square) | get_rook_rays(square),
+        Some(Piece::King) => {
+            // castling is written as the king capturing its rook
+            let rights = board.castle_rights(color);
+            let back_rank = Rank::First.relative_to(color);
+            [rights.short, rights.long]
+                .into_iter()
+                .flatten()
+                .fold(get_king_moves(square), |moves, file| {
+                    moves | Square::new(file, back_rank).bitboard()
+                })
+        }
+        None => BitBoard::EMPTY,
+    }
+}
+
+/// Pieces a pawn of `color` reaching `to` can promote to, shown on the squares of its file
+/// towards the player
+fn promotion_choices(to: Square, color: Color) -> [(Square, Piece); 4] {
+    [
+        (Rank::Eighth, Piece::Queen),
+        (Rank::Seventh, Piece::Knight),
+        (Rank::Sixth, Piece::Rook),
+        (Rank::Fifth, Piece::Bishop),
+    ]
+    .map(|(rank, piece)| (Square::new(to.file(), rank.relative_to(color)), piece))
+}
+
+/// Description of a square for screen readers, e.g. "e4, white knight"
+fn square_label(board: &ChessBoard, square: Square) -> String {
+    match board.piece_on(square).zip(board.color_on(square)) {
+        Some((piece, color)) => format!("{square}, {color:?} {piece:?}").to_lowercase(),
+        None => format!("{square}, empty"),
+    }
+}
+
+/// Number of plies played before reaching `board`
+fn ply(board: &ChessBoard) -> u32 {
+    (u32::from(board.fullmove_number()).saturating_sub(1)) * 2
+        + u32::from(board.side_to_move() == Color::Black)
+}
+
+impl Board {
+    /// Color at the bottom of the board
+    fn orientation(&self, props: &Props) -> Color {
+        if self.flipped {
+            !props.color
+        } else {
+            props.color
+        }
+    }
+
+    fn cursor(&self, props: &Props) -> Square {
+        self.cursor.unwrap_or(match self.orientation(props) {
+            Color::White => Square::A1,
+            Color::Black => Square::H8,
+        })
+    }
+
+    fn is_my_turn(props: &Props) -> bool {
+        props.free || props.board.side_to_move() == props.color
+    }
+
+    /// Plays the move if it's legal, or queues it as a premove during the opponent's turn.
+    /// Promotions open the piece picker, premoves always promote to a queen.
+    /// Returns whether the move was accepted.
+    fn try_move(&mut self, props: &Props, from: Square, to: Square) -> bool {
+        let promotes = props.board.piece_on(from) == Some(Piece::Pawn)
+            && props
+                .board
+                .color_on(from)
+                .is_some_and(|color| to.rank() == Rank::Eighth.relative_to(color));
+        let chess_move = Move {
+            from,
+            to,
+            promotion: promotes.then_some(Piece::Queen),
+        };
+        if Self::is_my_turn(props) {
+            if props.board.is_legal(chess_move) {
+                if promotes {
+                    let color = props.board.side_to_move();
+                    let choices: Vec<String> = promotion_choices(to, color)
+                        .into_iter()
+                        .map(|(square, piece)| format!("{piece:?} on {square}").to_lowercase())
+                        .collect();
+                    self.announcement = format!("Promote to {}", choices.join(", "));
+                    self.promotion = Some(chess_move);
+                    self.cursor = Some(to);
+                    self.focus_cursor = true;
+                } else {
+                    props.play_move.emit(chess_move);
+                }
+                self.selected_square = None;
+                return true;
+            }
+        } else if premove_destinations(&props.board, props.color, from).has(to) {
+            self.premove = Some(chess_move);
+            self.selected_square = None;
+            return true;
+        }
+        false
+    }
+
+    /// Plays the pending promotion with the piece offered on `square`, other squares cancel it
+    fn pick_promotion(&mut self, props: &Props, square: Square) {
+        let Some(pending) = self.promotion.take() else {
+            return;
+        };
+        let choice = promotion_choices(pending.to, props.board.side_to_move())
+            .into_iter()
+            .find(|&(choice, _)| choice == square);
+        if let Some((_, piece)) = choice {
+            let chess_move = Move {
+                promotion: Some(piece),
+                ..pending
+            };
+            if props.board.is_legal(chess_move) {
+                props.play_move.emit(chess_move);
+            }
+        }
+    }
 }
 
 impl Component for Board {
@@ -25,86 +281,70 @@ impl Component for Board {
     type Properties = Props;
 
     fn create(_ctx: &Context<Self>) -> Self {
-        Self::default()
+        Self {
+            appearance: Appearance::load(),
+            ..Self::default()
+        }
     }
 
     fn view(&self, ctx: &Context<Self>) -> Html {
         let props = ctx.props();
+        let orientation = self.orientation(props);
+        let (light, dark) = self.appearance.board_theme.colors();
 
-        let squares: Html = {
-            let click_square = {
-                let link = ctx.link().clone();
-                Callback::from(move |e: MouseEvent| {
-                    info!("click square");
-                    let link = link.clone();
-                    let element: HtmlElement = e.target_unchecked_into();
-                    if let Some(data) = element.get_attribute("data-square") {
-                        let chars: Vec<_> = data.chars().collect();
-                        if let [file, rank] = chars[..] {
-                            let file = file.to_digit(10);
-                            let rank = rank.to_digit(10);
-                            match file.zip(rank) {
-                                Some((file, rank)) => {
-                                    let square = Square::new(
-                                        File::index(file as usize),
-                                        Rank::index(rank as usize),
-                                    );
-                                    link.send_message(Msg::ClickSquare(square));
-                                }
-                                None => {
-                                    info!("invalid data-square value '{data}'");
-                                }
-                            }
-                        } else {
-                            info!("invalid data-square value '{data}'");
-                        };
-                    }
-                })
-            };
-            let click_square = &click_square;
-            (0..8)
-            .flat_map(|rank| {
-                (0..8).map(move |file| {
-                    html! {
-                        <div style={format!("background-color: {}; position: absolute; left: {}%; bottom: {}%; background-size: 100%; height: 12.5%; width: 12.5%;", if (file + rank) % 2 == 0 {"black"} else {"white"}, 12.5 * file as f32, 12.5 * rank as f32)}
-                        data-square={format!("{file}{rank}")}
-                        onclick={click_square.clone()}/>
-                    }
-                })
+        let cursor = self.cursor(props);
+        let squares: Html = Square::ALL
+            .into_iter()
+            .map(|square| {
+                let mut label = square_label(&props.board, square);
+                if self.selected_square == Some(square) {
+                    label.push_str(", selected");
+                }
+                // only the cursor can be reached with tab, the arrow keys move it
+                let (tabindex, node_ref) = if square == cursor {
+                    ("0", self.cursor_ref.clone())
+                } else {
+                    ("-1", NodeRef::default())
+                };
+                html! {
+                    <div
+                        ref={node_ref}
+                        role="button"
+                        aria-label={label}
+                        {tabindex}
+                        style={format!("background-color: {}; position: absolute; {} background-size: 100%; height: 12.5%; width: 12.5%; outline-offset: -3px;", if is_dark(square) {dark} else {light}, placement(square, orientation))}
+                    />
+                }
             })
-            .collect()
-        };
+            .collect();
 
-        let pieces: Html = {
-            let click_piece = {
-                let link = ctx.link().clone();
-                Callback::from(move |e: MouseEvent| {
-                    let link = link.clone();
-                    let element: HtmlElement = e.target_unchecked_into();
-                    if let Some(data) = element.get_attribute("data-square") {
-                        let chars: Vec<_> = data.chars().collect();
-                        if let [file, rank] = chars[..] {
-                            let file = file.to_digit(10);
-                            let rank = rank.to_digit(10);
-                            match file.zip(rank) {
-                                Some((file, rank)) => {
-                                    let square = Square::new(
-                                        File::index(file as usize),
-                                        Rank::index(rank as usize),
-                                    );
-                                    link.send_message(Msg::ClickSquare(square));
-                                }
-                                None => {
-                                    info!("invalid data-square value '{data}'");
-                                }
-                            }
-                        } else {
-                            info!("invalid data-square value '{data}'");
-                        };
-                    }
+        // file letters along the bottom edge, rank numbers along the left edge
+        let coordinates: Html = Square::ALL
+            .into_iter()
+            .filter_map(|square| {
+                let (left, bottom) = offset(square, orientation);
+                if left != 0.0 && bottom != 0.0 {
+                    return None;
+                }
+                let style = format!(
+                    "position: absolute; {} height: 12.5%; width: 12.5%; color: {}; font-size: calc({BOARD_SIZE} / 40); pointer-events: none;",
+                    placement(square, orientation),
+                    if is_dark(square) { light } else { dark },
+                );
+                Some(html! {
+                    <div style={style} aria-hidden="true">
+                        if bottom == 0.0 {
+                            <span style="position: absolute; right: 4%; bottom: 2%;">{square.file()}</span>
+                        }
+                        if left == 0.0 {
+                            <span style="position: absolute; left: 4%; top: 2%;">{square.rank()}</span>
+                        }
+                    </div>
                 })
-            };
-            (0..8)
+            })
+            .collect();
+
+        let pieces: Html = (0..8)
             .flat_map(|rank| {
                 (0..8)
                     .map(move |file| {
@@ -118,24 +358,16 @@ impl Component for Board {
                             .map(|(a, b)| (a, b, square))
                     })
                     .map(|(piece, color, square)| {
-                        let url = format!(
-                            "https://www.chess.com/chess-themes/pieces/neo/150/{}{}.png",
-                            match color {
-                                Color::White => "w",
-                                Color::Black => "b",
-                            },
-                            match piece {
-                                Piece::Pawn => "p",
-                                Piece::Knight => "n",
-                                Piece::Bishop => "b",
-                                Piece::Rook => "r",
-                                Piece::Queen => "q",
-                                Piece::King => "k",
-                            }
-                        );
-                        let style = format!("background-image: url('{url}'); position: absolute; left: {}%; bottom: {}%; background-size: 100%; height: 12.5%; width: 12.5%; cursor: grab; {}",
-                            12.5 * square.file() as usize as f32,
-                            12.5 * square.rank() as usize as f32,
+                        let url = self.appearance.piece_set.url(color, piece);
+                        let placement = match self.drag {
+                            Some(drag) if drag.from == square => format!(
+                                "left: {}%; top: {}%; z-index: 1; cursor: grabbing;",
+                                drag.position.x - 6.25,
+                                drag.position.y - 6.25,
+                            ),
+                            _ => format!("{} cursor: grab;", placement(square, orientation)),
+                        };
+                        let style = format!("background-image: url('{url}'); position: absolute; {placement} background-size: 100%; height: 12.5%; width: 12.5%; pointer-events: none; {}",
                             if self.selected_square == Some(square) {
                                 "background-color: rgba(255, 255, 0, 0.8)"
                             } else {
@@ -143,66 +375,417 @@ impl Component for Board {
                             },
                         );
                         html! {
-                            <div style={style}
-                            data-square={format!("{}{}", square.file() as usize, square.rank() as usize)}
-                            onclick={click_piece.clone()}/>
+                            <div style={style} aria-hidden="true"/>
                         }
                     })
             })
-            .collect()
-        };
+            .collect();
+
+        let last_move: Option<Html> = props.last_move.map(|last_move| {
+            [last_move.from, last_move.to].into_iter().map(|square| {
+                let style = format!("position: absolute; {} height: 12.5%; width: 12.5%; background-color: rgba(155, 199, 0, 0.6); pointer-events: none;",
+                        placement(square, orientation));
+                html! {
+                    <div style={style}/>
+                }
+            }).collect()
+        });
+
+        let check: Option<Html> = (!props.board.checkers().is_empty()).then(|| {
+            let king = props.board.king(props.board.side_to_move());
+            let style = format!("position: absolute; {} height: 12.5%; width: 12.5%; background: radial-gradient(rgba(255, 0, 0, 1) 0%, rgba(231, 0, 0, 0.8) 25%, rgba(169, 0, 0, 0) 90%); pointer-events: none;",
+                    placement(king, orientation));
+            html! {
+                <div style={style}/>
+            }
+        });
+
+        let premove: Option<Html> = self.premove.map(|premove| {
+            [premove.from, premove.to].into_iter().map(|square| {
+                let style = format!("position: absolute; {} height: 12.5%; width: 12.5%; background-color: rgba(20, 85, 180, 0.6); pointer-events: none;",
+                        placement(square, orientation));
+                html! {
+                    <div style={style}/>
+                }
+            }).collect()
+        });
 
         let moves: Option<Html> = self.selected_square.map(|selected_square| {
             let mut dest_squares = BitBoard::default();
-            ctx.props()
-                .board
-                .generate_moves_for(selected_square.bitboard(), |moves| {
-                    dest_squares |= moves.to;
-                    false
-                });
+            if Self::is_my_turn(props) {
+                props
+                    .board
+                    .generate_moves_for(selected_square.bitboard(), |moves| {
+                        dest_squares |= moves.to;
+                        false
+                    });
+            } else {
+                dest_squares = premove_destinations(&props.board, props.color, selected_square);
+            }
+            let Some(mover) = props.board.color_on(selected_square) else {
+                return Html::default();
+            };
+            let pawn = props.board.piece_on(selected_square) == Some(Piece::Pawn);
             dest_squares.into_iter().map(|dest| {
-                let style = format!("position: absolute; left: {}%; bottom: {}%; height: 12.5%; width: 12.5%; background-color: #bbb; border-radius: 50%; pointer-events: none;",
-                        12.5 * dest.file() as usize as f32,
-                        12.5 * dest.rank() as usize as f32);
+                // pawns only change files when capturing, including en passant
+                let capture = props.board.colors(!mover).has(dest)
+                    || (pawn && dest.file() != selected_square.file());
+                let marker = if capture {
+                    format!("border: calc({BOARD_SIZE} / 100) solid #bbb; box-sizing: border-box;")
+                } else {
+                    "background-color: #bbb; background-clip: content-box; padding: 4.5%; box-sizing: border-box;".to_string()
+                };
+                let style = format!("position: absolute; {} height: 12.5%; width: 12.5%; {marker} border-radius: 50%; pointer-events: none;",
+                        placement(dest, orientation));
                     html! {
                         <div style={style}/>
                     }
             }).collect()
         });
 
+        let promotion: Option<Html> = self.promotion.map(|pending| {
+            let color = props.board.side_to_move();
+            let choices: Html = promotion_choices(pending.to, color)
+                .into_iter()
+                .map(|(square, piece)| {
+                    let url = self.appearance.piece_set.url(color, piece);
+                    let style = format!("position: absolute; {} height: 12.5%; width: 12.5%; background: url('{url}') center / 90% no-repeat, radial-gradient(#fff 55%, #999); border-radius: 50%; cursor: pointer;",
+                        placement(square, orientation));
+                    html! {
+                        <div style={style}/>
+                    }
+                })
+                .collect();
+            html! {
+                <div style="position: absolute; inset: 0; z-index: 2; background-color: rgba(250, 250, 250, 0.6);" aria-hidden="true">
+                    {choices}
+                </div>
+            }
+        });
+
+        let cancel_premove = ctx.link().callback(|e: MouseEvent| {
+            e.prevent_default();
+            Msg::CancelPremove
+        });
+        let pointer_down = {
+            let board = self.board_ref.clone();
+            ctx.link().batch_callback(move |e: PointerEvent| {
+                // other buttons open the context menu, which cancels premoves
+                if e.button() != 0 {
+                    return None;
+                }
+                // keep receiving events when the pointer leaves the board while dragging
+                if let Some(element) = board.cast::<Element>() {
+                    _ = element.set_pointer_capture(e.pointer_id());
+                }
+                Position::of(&e, &board).map(Msg::PointerDown)
+            })
+        };
+        let pointer_move = {
+            let board = self.board_ref.clone();
+            ctx.link().batch_callback(move |e: PointerEvent| {
+                Position::of(&e, &board).map(Msg::PointerMove)
+            })
+        };
+        let pointer_up = {
+            let board = self.board_ref.clone();
+            ctx.link()
+                .batch_callback(move |e: PointerEvent| Position::of(&e, &board).map(Msg::PointerUp))
+        };
+        let pointer_cancel = ctx.link().callback(|_| Msg::PointerCancel);
+        let keydown = ctx.link().batch_callback(|e: KeyboardEvent| {
+            let msg = match e.key().as_str() {
+                "ArrowLeft" => Msg::MoveCursor { right: -1, up: 0 },
+                "ArrowRight" => Msg::MoveCursor { right: 1, up: 0 },
+                "ArrowUp" => Msg::MoveCursor { right: 0, up: 1 },
+                "ArrowDown" => Msg::MoveCursor { right: 0, up: -1 },
+                "Enter" | " " => Msg::Activate,
+                "Escape" => Msg::CancelPremove,
+                _ => return None,
+            };
+            // keep the page from scrolling
+            e.prevent_default();
+            // the analysis board navigates its moves with the arrow keys
+            e.stop_propagation();
+            Some(msg)
+        });
+        let submit_move = ctx.link().callback(|e: SubmitEvent| {
+            e.prevent_default();
+            Msg::SubmitMove
+        });
+        let flip = ctx.link().callback(|_| Msg::Flip);
+        let muted = alerts::muted();
+        let toggle_sound = ctx.link().callback(|_| Msg::ToggleSound);
+        let set_piece_set = ctx.link().batch_callback(|e: Event| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            PieceSet::from_id(&select.value()).map(Msg::SetPieceSet)
+        });
+        let set_board_theme = ctx.link().batch_callback(|e: Event| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            BoardTheme::from_id(&select.value()).map(Msg::SetBoardTheme)
+        });
+        let piece_sets: Html = PieceSet::ALL
+            .into_iter()
+            .map(|set| {
+                html! {
+                    <option value={set.id()} selected={set == self.appearance.piece_set}>
+                        {set.name()}
+                    </option>
+                }
+            })
+            .collect();
+        let board_themes: Html = BoardTheme::ALL
+            .into_iter()
+            .map(|theme| {
+                html! {
+                    <option value={theme.id()} selected={theme == self.appearance.board_theme}>
+                        {theme.name()}
+                    </option>
+                }
+            })
+            .collect();
+
         html! {
             <>
-                <p>{"You are "}{if props.color == Color::White {"White"} else {"Black"}}</p>
-                <div style="width: 600px; height: 600px; position: relative;">
+                <p>
+                    if !props.free {
+                        {"You are "}{if props.color == Color::White {"White"} else {"Black"}}
+                        {" "}
+                    }
+                    <button onclick={flip}>{"Flip board"}</button>
+                    {" "}
+                    <button onclick={toggle_sound} aria-pressed={muted.to_string()}>
+                        {if muted {"Unmute"} else {"Mute"}}
+                    </button>
+                    {" "}
+                    <select onchange={set_piece_set}>{piece_sets}</select>
+                    <select onchange={set_board_theme}>{board_themes}</select>
+                </p>
+                <div
+                    ref={&self.board_ref}
+                    role="group"
+                    aria-label="Chess board, use the arrow keys to move between squares and enter to pick up or drop a piece"
+                    style={format!("width: {BOARD_SIZE}; height: {BOARD_SIZE}; position: relative; touch-action: none; user-select: none;")}
+                    onkeydown={keydown}
+                    oncontextmenu={cancel_premove}
+                    onpointerdown={pointer_down}
+                    onpointermove={pointer_move}
+                    onpointerup={pointer_up}
+                    onpointercancel={pointer_cancel}
+                >
                     {squares}
+                    {coordinates}
+                    {last_move}
+                    {check}
+                    {premove}
                     {pieces}
                     {moves}
+                    {promotion}
+                </div>
+                <form onsubmit={submit_move}>
+                    <input
+                        ref={&self.move_input_ref}
+                        aria-label="Move in SAN or UCI notation"
+                        placeholder="Type a move, e.g. Nf3 or g1f3"
+                        disabled={props.disabled}
+                    />
+                    <button type="submit" disabled={props.disabled}>{"Play"}</button>
+                </form>
+                if let Some(err) = &self.move_error {
+                    <p role="alert">{err}</p>
+                }
+                <div aria-live="polite" style="position: absolute; width: 1px; height: 1px; overflow: hidden; clip: rect(0 0 0 0);">
+                    {&self.announcement}
                 </div>
             </>
         }
     }
 
+    fn changed(&mut self, ctx: &Context<Self>, old_props: &Self::Properties) -> bool {
+        let props = ctx.props();
+        if props.disabled {
+            self.selected_square = None;
+            self.drag = None;
+            self.premove = None;
+            self.promotion = None;
+        }
+        if props.board != old_props.board {
+            self.promotion = None;
+            // the position also changes on takebacks and when jumping through an analysis
+            let moved = ply(&props.board) == ply(&old_props.board) + 1;
+            let mover = old_props.board.side_to_move();
+            if let Some(last_move) = props.last_move.filter(|_| moved) {
+                alerts::play(Sound::of(&old_props.board, last_move));
+            }
+            match props.last_move {
+                Some(last_move) if moved && (props.free || mover != props.color) => {
+                    let san = notation::to_san(&old_props.board, last_move);
+                    self.announcement = format!("{mover:?} played {san}");
+                }
+                _ => {}
+            }
+            if let Some(premove) = self.premove.take() {
+                let opponent_moved = moved && props.board.side_to_move() == props.color;
+                if opponent_moved && props.board.is_legal(premove) {
+                    props.play_move.emit(premove);
+                } else {
+                    info!("dropping premove {premove}");
+                }
+            }
+        }
+        true
+    }
+
     fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        let props = ctx.props();
         match msg {
-            Msg::ClickSquare(clicked) => {
+            Msg::PointerDown(_) if props.disabled => false,
+            Msg::PointerDown(position) if self.promotion.is_some() => {
+                match position.square(self.orientation(props)) {
+                    Some(square) => self.pick_promotion(props, square),
+                    None => self.promotion = None,
+                }
+                true
+            }
+            Msg::PointerDown(position) => {
+                let Some(square) = position.square(self.orientation(props)) else {
+                    return false;
+                };
+                // clicking focuses the square, so keyboard navigation continues from there
+                self.cursor = Some(square);
                 if let Some(selected) = self.selected_square {
-                    let chess_move = Move {
-                        from: selected,
-                        to: clicked,
-                        promotion: None,
-                    };
-                    if ctx.props().board.is_legal(chess_move) {
-                        ctx.props().play_move.emit(chess_move);
+                    if selected != square && self.try_move(props, selected, square) {
                         return true;
                     }
                 }
-                if ctx.props().board.piece_on(clicked).is_some() {
-                    self.selected_square.replace(clicked);
+                if props.board.piece_on(square).is_some() {
+                    self.selected_square = Some(square);
+                    self.drag = Some(Drag {
+                        from: square,
+                        position,
+                    });
                     true
                 } else {
-                    false
+                    self.selected_square.take().is_some()
+                }
+            }
+            Msg::PointerMove(position) => match &mut self.drag {
+                Some(drag) => {
+                    drag.position = position;
+                    true
+                }
+                None => false,
+            },
+            Msg::PointerUp(position) => {
+                let Some(drag) = self.drag.take() else {
+                    return false;
+                };
+                // dropping the piece on its own square keeps it selected for click-to-move,
+                // illegal drops snap back
+                if let Some(to) = position
+                    .square(self.orientation(props))
+                    .filter(|&to| to != drag.from)
+                {
+                    self.try_move(props, drag.from, to);
                 }
+                true
+            }
+            Msg::PointerCancel => self.drag.take().is_some(),
+            Msg::Flip => {
+                self.flipped = !self.flipped;
+                true
+            }
+            Msg::SetPieceSet(piece_set) => {
+                self.appearance.piece_set = piece_set;
+                self.appearance.save();
+                true
             }
+            Msg::SetBoardTheme(board_theme) => {
+                self.appearance.board_theme = board_theme;
+                self.appearance.save();
+                true
+            }
+            Msg::CancelPremove => {
+                let changed = self.premove.is_some()
+                    || self.selected_square.is_some()
+                    || self.promotion.is_some();
+                self.premove = None;
+                self.selected_square = None;
+                self.promotion = None;
+                changed
+            }
+            Msg::ToggleSound => {
+                alerts::set_muted(!alerts::muted());
+                true
+            }
+            Msg::MoveCursor { right, up } => {
+                let cursor = self.cursor(props);
+                // screen directions are mirrored when black is at the bottom
+                let (right, up) = match self.orientation(props) {
+                    Color::White => (right, up),
+                    Color::Black => (-right, -up),
+                };
+                let file = cursor.file() as i8 + right;
+                let rank = cursor.rank() as i8 + up;
+                if !(0..8).contains(&file) || !(0..8).contains(&rank) {
+                    return false;
+                }
+                self.cursor = Some(Square::new(
+                    File::index(file as usize),
+                    Rank::index(rank as usize),
+                ));
+                self.focus_cursor = true;
+                true
+            }
+            Msg::Activate if props.disabled => false,
+            Msg::Activate if self.promotion.is_some() => {
+                self.pick_promotion(props, self.cursor(props));
+                true
+            }
+            Msg::Activate => {
+                let square = self.cursor(props);
+                if let Some(selected) = self.selected_square {
+                    if selected != square && self.try_move(props, selected, square) {
+                        return true;
+                    }
+                }
+                self.selected_square = (self.selected_square != Some(square)
+                    && props.board.piece_on(square).is_some())
+                .then_some(square);
+                true
+            }
+            Msg::SubmitMove => {
+                let Some(input) = self.move_input_ref.cast::<HtmlInputElement>() else {
+                    return false;
+                };
+                let text = input.value();
+                if text.trim().is_empty() || props.disabled {
+                    return false;
+                }
+                if !Self::is_my_turn(props) {
+                    self.move_error = Some("It's not your turn".to_string());
+                    return true;
+                }
+                match notation::parse_move(&props.board, &text) {
+                    Ok(chess_move) => {
+                        props.play_move.emit(chess_move);
+                        input.set_value("");
+                        self.selected_square = None;
+                        self.move_error = None;
+                    }
+                    Err(err) => self.move_error = Some(err.to_string()),
+                }
+                true
+            }
+        }
+    }
+
+    fn rendered(&mut self, _ctx: &Context<Self>, _first_render: bool) {
+        if !std::mem::take(&mut self.focus_cursor) {
+            return;
+        }
+        if let Some(square) = self.cursor_ref.cast::<HtmlElement>() {
+            _ = square.focus();
         }
     }
 }
@@ -0,0 +1,337 @@
+use cozy_chess::{
+    Board as ChessBoard, BoardBuilder, CastleRights, Color, File, GameStatus, Piece, Rank, Square,
+};
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+
+use super::{
+    board::{is_dark, placement, BOARD_SIZE, LAYOUT, SIDE_PANEL},
+    host::Host,
+};
+use crate::appearance::Appearance;
+
+/// Sets up a position to export as FEN or to host a game from
+pub struct Editor {
+    builder: BoardBuilder,
+    /// Piece placed by clicking a square, `None` removes pieces instead
+    tool: Option<(Piece, Color)>,
+    /// Set once the position is hosted, `true` to play against the computer
+    hosting: Option<bool>,
+    appearance: Appearance,
+    fen_ref: NodeRef,
+    error: Option<String>,
+}
+
+pub enum Msg {
+    SetTool(Option<(Piece, Color)>),
+    /// Places the selected piece, or removes the piece if it's already there
+    Place(Square),
+    Remove(Square),
+    SetSideToMove(Color),
+    SetCastling {
+        color: Color,
+        short: bool,
+        allowed: bool,
+    },
+    SetEnPassant(Option<File>),
+    StartPosition,
+    Clear,
+    LoadFen,
+    Host {
+        bot: bool,
+    },
+}
+
+/// Rights for castling with the rooks on their initial squares
+fn castle_rights(short: bool, long: bool) -> CastleRights {
+    CastleRights {
+        short: short.then_some(File::H),
+        long: long.then_some(File::A),
+    }
+}
+
+impl Editor {
+    /// The position if it's valid and the game isn't already over
+    fn position(&self) -> Result<ChessBoard, String> {
+        let board = self.builder.build().map_err(|err| err.to_string())?;
+        match board.status() {
+            GameStatus::Ongoing => Ok(board),
+            GameStatus::Won => Err("The side to move is checkmated.".to_string()),
+            GameStatus::Drawn => Err("The game is already drawn.".to_string()),
+        }
+    }
+
+    /// En passant is only possible on the third rank from the perspective of the side that
+    /// just moved
+    fn en_passant_square(&self, file: File) -> Square {
+        Square::new(file, Rank::Third.relative_to(!self.builder.side_to_move))
+    }
+
+    fn palette(&self, ctx: &Context<Self>, color: Color) -> Html {
+        let (light, _) = self.appearance.board_theme.colors();
+        Piece::ALL
+            .into_iter()
+            .map(|piece| {
+                let tool = Some((piece, color));
+                let onclick = ctx.link().callback(move |_| Msg::SetTool(tool));
+                let border = if self.tool == tool {
+                    "#1455b4"
+                } else {
+                    "transparent"
+                };
+                let style = format!(
+                    "background-image: url('{}'); background-size: 100%; background-color: {light}; width: calc({BOARD_SIZE} / 8); height: calc({BOARD_SIZE} / 8); min-width: 44px; min-height: 44px; border: 3px solid {border};",
+                    self.appearance.piece_set.url(color, piece),
+                );
+                html! {
+                    <button {style} {onclick} title={format!("{color:?} {piece:?}")}/>
+                }
+            })
+            .collect()
+    }
+}
+
+impl Component for Editor {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            builder: BoardBuilder::startpos(),
+            tool: Some((Piece::Pawn, Color::White)),
+            hosting: None,
+            appearance: Appearance::load(),
+            fen_ref: NodeRef::default(),
+            error: None,
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        if let Some(bot) = self.hosting {
+            let fen = self.position().ok().map(|board| board.to_string());
+            return html! { <Host {bot} {fen}/> };
+        }
+
+        let (light, dark) = self.appearance.board_theme.colors();
+        let squares: Html = Square::ALL
+            .into_iter()
+            .map(|square| {
+                let onclick = ctx.link().callback(move |_| Msg::Place(square));
+                let oncontextmenu = ctx.link().callback(move |e: MouseEvent| {
+                    e.prevent_default();
+                    Msg::Remove(square)
+                });
+                let style = format!(
+                    "background-color: {}; position: absolute; {} height: 12.5%; width: 12.5%;",
+                    if is_dark(square) { dark } else { light },
+                    placement(square, Color::White),
+                );
+                html! {
+                    <div {style} {onclick} {oncontextmenu}/>
+                }
+            })
+            .collect();
+        let pieces: Html = Square::ALL
+            .into_iter()
+            .filter_map(|square| {
+                let (piece, color) = self.builder.square(square)?;
+                let style = format!(
+                    "background-image: url('{}'); position: absolute; {} background-size: 100%; height: 12.5%; width: 12.5%; pointer-events: none;",
+                    self.appearance.piece_set.url(color, piece),
+                    placement(square, Color::White),
+                );
+                Some(html! { <div {style}/> })
+            })
+            .collect();
+
+        let erase = ctx.link().callback(|_| Msg::SetTool(None));
+        let set_side_to_move = ctx.link().callback(|e: Event| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            Msg::SetSideToMove(if select.value() == "black" {
+                Color::Black
+            } else {
+                Color::White
+            })
+        });
+        let castling: Html = [
+            (Color::White, true, "White O-O"),
+            (Color::White, false, "White O-O-O"),
+            (Color::Black, true, "Black O-O"),
+            (Color::Black, false, "Black O-O-O"),
+        ]
+        .into_iter()
+        .map(|(color, short, label)| {
+            let rights = self.builder.castle_rights(color);
+            let checked = if short {
+                rights.short.is_some()
+            } else {
+                rights.long.is_some()
+            };
+            let onchange = ctx.link().callback(move |e: Event| {
+                let input: HtmlInputElement = e.target_unchecked_into();
+                Msg::SetCastling {
+                    color,
+                    short,
+                    allowed: input.checked(),
+                }
+            });
+            html! {
+                <label>
+                    <input type="checkbox" {checked} {onchange}/>
+                    {label}{" "}
+                </label>
+            }
+        })
+        .collect();
+        let en_passant_file = self.builder.en_passant.map(Square::file);
+        let set_en_passant = ctx.link().callback(|e: Event| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            Msg::SetEnPassant(select.value().parse().ok())
+        });
+        let en_passant_files: Html = File::ALL
+            .into_iter()
+            .map(|file| {
+                html! {
+                    <option value={file.to_string()} selected={en_passant_file == Some(file)}>
+                        {file}
+                    </option>
+                }
+            })
+            .collect();
+        let load_fen = ctx.link().callback(|e: SubmitEvent| {
+            e.prevent_default();
+            Msg::LoadFen
+        });
+
+        let position = self.position();
+        let valid = position.is_ok();
+        let fen = match &position {
+            Ok(board) => html! {
+                <input readonly=true size="60" value={board.to_string()}/>
+            },
+            Err(err) => html! { <p>{"Invalid position: "}{err}</p> },
+        };
+
+        html! {
+            <div>
+                <h3>{"Board editor"}</h3>
+                <p>{"Pick a piece and tap squares to place it. Tapping a square with the same piece or right clicking removes it."}</p>
+                <div style={LAYOUT}>
+                    <div>
+                        <div>{self.palette(ctx, Color::White)}</div>
+                        <div
+                            style={format!("width: {BOARD_SIZE}; height: {BOARD_SIZE}; position: relative; user-select: none;")}
+                        >
+                            {squares}
+                            {pieces}
+                        </div>
+                        <div>{self.palette(ctx, Color::Black)}</div>
+                    </div>
+                    <div style={SIDE_PANEL}>
+                        <p>
+                            <button onclick={erase} disabled={self.tool.is_none()}>{"Remove pieces"}</button>
+                            {" "}
+                            <button onclick={ctx.link().callback(|_| Msg::StartPosition)}>{"Initial position"}</button>
+                            <button onclick={ctx.link().callback(|_| Msg::Clear)}>{"Clear board"}</button>
+                        </p>
+                        <p>
+                            <select onchange={set_side_to_move}>
+                                <option value="white" selected={self.builder.side_to_move == Color::White}>
+                                    {"White to move"}
+                                </option>
+                                <option value="black" selected={self.builder.side_to_move == Color::Black}>
+                                    {"Black to move"}
+                                </option>
+                            </select>
+                            {" En passant: "}
+                            <select onchange={set_en_passant}>
+                                <option value="-" selected={en_passant_file.is_none()}>{"-"}</option>
+                                {en_passant_files}
+                            </select>
+                        </p>
+                        <p>{castling}</p>
+                        {fen}
+                        <form onsubmit={load_fen}>
+                            <input placeholder={"FEN"} ref={&self.fen_ref} size="60"/>
+                            <button type={"submit"}>{"Load FEN"}</button>
+                        </form>
+                        if let Some(err) = &self.error {
+                            <p>{err}</p>
+                        }
+                        <p>
+                            <button onclick={ctx.link().callback(|_| Msg::Host { bot: false })} disabled={!valid}>
+                                {"Host game"}
+                            </button>
+                            <button onclick={ctx.link().callback(|_| Msg::Host { bot: true })} disabled={!valid}>
+                                {"Play the computer"}
+                            </button>
+                        </p>
+                    </div>
+                </div>
+            </div>
+        }
+    }
+
+    fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Msg::SetTool(tool) => self.tool = tool,
+            Msg::Place(square) => {
+                let current = self.builder.square_mut(square);
+                *current = match self.tool {
+                    Some(tool) if *current != Some(tool) => Some(tool),
+                    _ => None,
+                };
+            }
+            Msg::Remove(square) => *self.builder.square_mut(square) = None,
+            Msg::SetSideToMove(color) => {
+                self.builder.side_to_move = color;
+                // keep the file, the square moves to the other side of the board
+                if let Some(square) = self.builder.en_passant {
+                    self.builder.en_passant = Some(self.en_passant_square(square.file()));
+                }
+            }
+            Msg::SetCastling {
+                color,
+                short,
+                allowed,
+            } => {
+                let rights = self.builder.castle_rights_mut(color);
+                *rights = if short {
+                    castle_rights(allowed, rights.long.is_some())
+                } else {
+                    castle_rights(rights.short.is_some(), allowed)
+                };
+            }
+            Msg::SetEnPassant(file) => {
+                self.builder.en_passant = file.map(|file| self.en_passant_square(file));
+            }
+            Msg::StartPosition => {
+                self.builder = BoardBuilder::startpos();
+                self.error = None;
+            }
+            Msg::Clear => {
+                self.builder = BoardBuilder::empty();
+                self.error = None;
+            }
+            Msg::LoadFen => {
+                let Some(input) = self.fen_ref.cast::<HtmlInputElement>() else {
+                    return false;
+                };
+                match input.value().trim().parse::<ChessBoard>() {
+                    Ok(board) => {
+                        self.builder = BoardBuilder::from_board(&board);
+                        self.error = None;
+                    }
+                    Err(err) => self.error = Some(format!("Invalid FEN: {err}")),
+                }
+            }
+            Msg::Host { bot } => {
+                if self.position().is_err() {
+                    return false;
+                }
+                self.hosting = Some(bot);
+            }
+        }
+        true
+    }
+}
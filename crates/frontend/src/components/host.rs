@@ -1,4 +1,4 @@
-use common::http::HostResponse;
+use common::http::{BotSettings, HostRequest, HostResponse, LobbySettings};
 use reqwasm::http::Request;
 use uuid::Uuid;
 use yew::prelude::*;
@@ -11,6 +11,18 @@ pub struct Host {
     pub error: Option<String>,
 }
 
+#[derive(Debug, Clone, PartialEq, Properties)]
+pub struct Props {
+    /// Play against the server's engine instead of waiting for an opponent
+    #[prop_or_default]
+    pub bot: bool,
+    #[prop_or_default]
+    pub settings: LobbySettings,
+    /// Start from this position instead of the initial one
+    #[prop_or_default]
+    pub fen: Option<String>,
+}
+
 pub enum Msg {
     Error(String),
     Join { id: Uuid, session: Uuid },
@@ -18,11 +30,21 @@ pub enum Msg {
 
 impl Component for Host {
     type Message = Msg;
-    type Properties = ();
+    type Properties = Props;
 
     fn create(ctx: &yew::Context<Self>) -> Self {
-        ctx.link().send_future(async {
-            let response = match Request::post("/api/host").send().await {
+        let request = HostRequest {
+            bot: ctx.props().bot.then(BotSettings::default),
+            settings: ctx.props().settings,
+            fen: ctx.props().fen.clone(),
+        };
+        ctx.link().send_future(async move {
+            let response = match Request::post("/api/host")
+                .header("Content-Type", "application/json")
+                .body(serde_json::to_string(&request).unwrap())
+                .send()
+                .await
+            {
                 Ok(response) => response,
                 Err(err) => {
                     return Msg::Error(err.to_string());
@@ -36,6 +58,8 @@ impl Component for Host {
                         session: response.session,
                     }
                 }
+                400 => Msg::Error("The server rejected the game settings".to_string()),
+                401 => Msg::Error("Log in to host rated games".to_string()),
                 other => Msg::Error(format!(
                     "Unhandled status code {other} ({})",
                     response.status_text(),
@@ -64,10 +88,13 @@ impl Component for Host {
                 true
             }
             Msg::Join { id, session } => {
-                ctx.link()
-                    .navigator()
-                    .unwrap()
-                    .push(&Route::WaitingForOpponent { id, session });
+                // the engine takes its seat right away, so there is nothing to wait for
+                let route = if ctx.props().bot {
+                    Route::Ingame { id, session }
+                } else {
+                    Route::WaitingForOpponent { id, session }
+                };
+                ctx.link().navigator().unwrap().push(&route);
                 false
             }
         }
@@ -1,18 +1,26 @@
-use common::ws::{message::Message, GameState};
+use common::{
+    notation,
+    ws::{message::Message, GameOverReason, GameResult, GameState, RatingChange},
+};
 
-use super::board::Board;
+use super::board::{Board, LAYOUT, SIDE_PANEL};
+use crate::{alerts, Route};
 
 use common::ws::{ClientMsg, ServerMsg};
-use cozy_chess::{Board as ChessBoard, Color, Move};
+use cozy_chess::{Board as ChessBoard, Color, GameStatus, Move};
 use futures::{
     channel::mpsc::{channel, Sender},
     SinkExt, StreamExt,
 };
+use js_sys::Date;
 use log::{info, warn};
 use reqwasm::websocket::futures::WebSocket;
+use std::time::Duration;
 use uuid::Uuid;
 use wasm_bindgen_futures::spawn_local;
-use yew::{html, Callback, Component, Html, Properties};
+use web_sys::HtmlInputElement;
+use yew::{html, platform::time::interval, Component, Event, Html, Properties, TargetCast};
+use yew_router::prelude::*;
 
 #[derive(Properties, PartialEq)]
 pub struct Props {
@@ -24,15 +32,176 @@ pub struct Props {
 pub struct Game {
     pub board: ChessBoard,
     pub color: Color,
+    pub last_move: Option<Move>,
 }
 
 pub struct Ingame {
     game: Option<Game>,
+    /// Display names of the white and black player
+    players: Option<[String; Color::NUM]>,
+    /// Set once the server reported the end of the game
+    game_over: Option<GameOver>,
+    /// Hides the result so the final position can be seen
+    hide_result: bool,
+    /// Player who offered a rematch
+    rematch: Option<Color>,
+    /// Player waiting for an answer to their takeback request
+    takeback: Option<Color>,
+    /// Shown below the board until the next move
+    notice: Option<String>,
+    /// Remaining time of white and black as last sent by the server
+    clocks: Option<[Duration; Color::NUM]>,
+    /// When the side to move's clock started counting down, in milliseconds since the epoch
+    clock_started: Option<f64>,
     tx: Sender<ClientMsg>,
 }
 
+/// Result of a finished game as seen by one player
+#[derive(Debug, Clone)]
+struct GameOver {
+    title: &'static str,
+    reason: String,
+    rating: Option<RatingChange>,
+}
+
+impl GameOver {
+    fn new(
+        result: GameResult,
+        reason: GameOverReason,
+        ratings: Option<[RatingChange; 2]>,
+        color: Color,
+    ) -> Self {
+        let winner = match result {
+            GameResult::WhiteWins => Some(Color::White),
+            GameResult::BlackWins => Some(Color::Black),
+            GameResult::Draw => None,
+        };
+        let title = match winner {
+            Some(winner) if winner == color => "You won",
+            Some(_) => "You lost",
+            None => "Draw",
+        };
+        Self {
+            title,
+            reason: format!("by {reason}"),
+            rating: ratings.map(|ratings| ratings[color as usize]),
+        }
+    }
+}
+
 pub enum Msg {
-    ReceivedMsg(ServerMsg),
+    Received(ServerMsg),
+    Send(ClientMsg),
+    ShowResult(bool),
+    SetNotifications(bool),
+    /// The notification setting was stored and the browser answered the permission request
+    NotificationsChanged,
+    /// Redraws a running clock
+    Tick,
+}
+
+fn format_clock(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{:02}:{:02}", secs / 60, secs % 60)
+}
+
+impl Ingame {
+    fn is_my_turn(&self) -> bool {
+        self.game_over.is_none()
+            && self.game.as_ref().is_some_and(|game| {
+                game.board.side_to_move() == game.color
+                    && game.board.status() == GameStatus::Ongoing
+            })
+    }
+
+    /// Remaining time of `color`, empty in games without a time control
+    fn clock(&self, color: Color) -> String {
+        let Some(clocks) = self.clocks else {
+            return String::new();
+        };
+        let mut remaining = clocks[color as usize];
+        if let (Some(started), Some(game), None) = (self.clock_started, &self.game, &self.game_over)
+        {
+            if game.board.side_to_move() == color {
+                let elapsed = (Date::now() - started).max(0.0) / 1000.0;
+                remaining = remaining.saturating_sub(Duration::from_secs_f64(elapsed));
+            }
+        }
+        format_clock(remaining)
+    }
+
+    fn send(&self, msg: ClientMsg) {
+        let mut tx = self.tx.clone();
+        spawn_local(async move {
+            tx.send(msg).await.unwrap();
+        });
+    }
+
+    fn result_overlay(&self, ctx: &yew::Context<Self>, color: Color) -> Html {
+        let Some(game_over) = &self.game_over else {
+            return html! {};
+        };
+        if self.hide_result {
+            return html! {
+                <button onclick={ctx.link().callback(|_| Msg::ShowResult(true))}>
+                    {"Show result"}
+                </button>
+            };
+        }
+        let rematch = ctx.link().callback(|_| Msg::Send(ClientMsg::OfferRematch));
+        let (rematch_label, rematch_offered) = match self.rematch {
+            None => ("Rematch", false),
+            Some(offered) if offered == color => ("Rematch offered", true),
+            Some(_) => ("Accept rematch", false),
+        };
+        let id = ctx.props().id;
+        html! {
+            <div style="position: absolute; inset: 0; z-index: 2; display: flex; align-items: center; justify-content: center; background-color: rgba(0, 0, 0, 0.4);">
+                <div style="background-color: white; padding: 1em 2em; text-align: center;">
+                    <h2>{game_over.title}</h2>
+                    <p>{&game_over.reason}</p>
+                    if let Some(RatingChange { rating, delta }) = game_over.rating {
+                        <p>{format!("Your rating is now {rating} ({delta:+})")}</p>
+                    }
+                    <p>
+                        <button onclick={rematch} disabled={rematch_offered}>{rematch_label}</button>
+                        <a href={format!("/api/game/{id}/pgn")} download={format!("{id}.pgn")}>
+                            {"Download PGN"}
+                        </a>
+                        {" "}
+                        <Link<Route> to={Route::AnalyzeGame { id }}>{"Analyze"}</Link<Route>>
+                        {" "}
+                        <Link<Route> to={Route::Home}>{"Menu"}</Link<Route>>
+                    </p>
+                    <button onclick={ctx.link().callback(|_| Msg::ShowResult(false))}>
+                        {"Show board"}
+                    </button>
+                </div>
+            </div>
+        }
+    }
+
+    fn takeback_controls(&self, ctx: &yew::Context<Self>, color: Color) -> Html {
+        if self.game_over.is_some() {
+            return html! {};
+        }
+        let send = |msg: ClientMsg| ctx.link().callback(move |_| Msg::Send(msg.clone()));
+        match self.takeback {
+            None => html! {
+                <button onclick={send(ClientMsg::RequestTakeback)}>{"Request takeback"}</button>
+            },
+            Some(requester) if requester == color => {
+                html! { <p>{"Waiting for your opponent to accept the takeback..."}</p> }
+            }
+            Some(_) => html! {
+                <p>
+                    {"Your opponent wants to take back their move "}
+                    <button onclick={send(ClientMsg::AcceptTakeback)}>{"Accept"}</button>
+                    <button onclick={send(ClientMsg::DeclineTakeback)}>{"Decline"}</button>
+                </p>
+            },
+        }
+    }
 }
 
 impl Component for Ingame {
@@ -46,6 +215,9 @@ impl Component for Ingame {
 
         let (tx, mut rx) = channel::<ClientMsg>(0);
 
+        ctx.link()
+            .send_stream(interval(Duration::from_millis(200)).map(|()| Msg::Tick));
+
         {
             let id = ctx.props().id;
             let session = ctx.props().session;
@@ -68,7 +240,7 @@ impl Component for Ingame {
                         match msg {
                             Ok(msg) => match Message::from_reqwasm_message(msg) {
                                 Ok(msg) => {
-                                    link.send_message(Msg::ReceivedMsg(msg));
+                                    link.send_message(Msg::Received(msg));
                                 }
                                 Err(err) => {
                                     info!("error deserializing message: {err}");
@@ -88,22 +260,61 @@ impl Component for Ingame {
             });
         }
 
-        Self { tx, game: None }
+        Self {
+            tx,
+            game: None,
+            players: None,
+            game_over: None,
+            hide_result: false,
+            rematch: None,
+            takeback: None,
+            notice: None,
+            clocks: None,
+            clock_started: None,
+        }
     }
 
-    fn view(&self, _ctx: &yew::Context<Self>) -> Html {
-        if let Some(Game { board, color }) = self.game.clone() {
-            let play_move = {
-                let tx = self.tx.clone();
-                Callback::from(move |chess_move: Move| {
-                    let mut tx = tx.clone();
-                    spawn_local(async move {
-                        tx.send(ClientMsg::PlayMove(chess_move)).await.unwrap();
-                    });
-                })
+    fn view(&self, ctx: &yew::Context<Self>) -> Html {
+        if let Some(Game {
+            board,
+            color,
+            last_move,
+        }) = self.game.clone()
+        {
+            let play_move = ctx
+                .link()
+                .callback(|chess_move: Move| Msg::Send(ClientMsg::PlayMove(chess_move)));
+            let name = |color: Color| match &self.players {
+                Some(players) => players[color as usize].clone(),
+                None => String::new(),
             };
+            let set_notifications = ctx.link().callback(|e: Event| {
+                let input: HtmlInputElement = e.target_unchecked_into();
+                Msg::SetNotifications(input.checked())
+            });
             html! {
-                <Board {board} {color} {play_move}/>
+                <div style={LAYOUT}>
+                    <div>
+                        <p>{name(!color)}{" "}<b>{self.clock(!color)}</b></p>
+                        <div style="position: relative; display: inline-block;">
+                            <Board {board} {color} {last_move} {play_move} disabled={self.game_over.is_some()}/>
+                            {self.result_overlay(ctx, color)}
+                        </div>
+                        <p>{name(color)}{" "}<b>{self.clock(color)}</b></p>
+                    </div>
+                    <div style={SIDE_PANEL}>
+                        {self.takeback_controls(ctx, color)}
+                        if let Some(notice) = &self.notice {
+                            <p>{notice}</p>
+                        }
+                        <p>
+                            <label>
+                                <input type="checkbox" checked={alerts::notifications()} onchange={set_notifications}/>
+                                {" Notify me when it's my turn"}
+                            </label>
+                        </p>
+                    </div>
+                </div>
             }
         } else {
             html! {
@@ -112,31 +323,131 @@ impl Component for Ingame {
         }
     }
 
-    fn update(&mut self, _ctx: &yew::Context<Self>, msg: Self::Message) -> bool {
+    fn update(&mut self, ctx: &yew::Context<Self>, msg: Self::Message) -> bool {
         match msg {
-            Msg::ReceivedMsg(msg) => match msg {
+            Msg::Received(msg) => match msg {
                 ServerMsg::Connected(GameState::Ingame { fen, color }) => {
                     self.game.replace(Game {
                         board: fen.parse().unwrap(),
                         color,
+                        last_move: None,
                     });
                     true
                 }
                 ServerMsg::PlayedMove(chess_move) => {
+                    self.takeback = None;
+                    self.notice = None;
                     if let Some(game) = &mut self.game {
+                        let san = notation::to_san(&game.board, chess_move);
                         if let Err(err) = game.board.try_play(chess_move) {
                             warn!("tried to play invalid move {chess_move} ({err})");
                         };
+                        game.last_move = Some(chess_move);
+                        if game.board.side_to_move() == game.color {
+                            let opponent = match &self.players {
+                                Some(players) => players[!game.color as usize].as_str(),
+                                None => "Your opponent",
+                            };
+                            alerts::notify("Your turn", &format!("{opponent} played {san}"));
+                        }
                         true
                     } else {
                         false
                     }
                 }
+                ServerMsg::Players { white, black } => {
+                    self.players = Some([white, black]);
+                    true
+                }
+                ServerMsg::GameOver {
+                    result,
+                    reason,
+                    ratings,
+                } => match &self.game {
+                    Some(game) => {
+                        let game_over = GameOver::new(result, reason, ratings, game.color);
+                        alerts::notify(game_over.title, &game_over.reason);
+                        self.game_over = Some(game_over);
+                        true
+                    }
+                    None => false,
+                },
+                ServerMsg::RematchOffered(color) => {
+                    self.rematch = Some(color);
+                    true
+                }
+                ServerMsg::Rematch { lobby_id } => {
+                    ctx.link().navigator().unwrap().push(&Route::Ingame {
+                        id: lobby_id,
+                        session: ctx.props().session,
+                    });
+                    false
+                }
+                ServerMsg::TakebackRequested(color) => {
+                    self.takeback = Some(color);
+                    self.notice = None;
+                    true
+                }
+                ServerMsg::TakebackDeclined => {
+                    self.takeback = None;
+                    self.notice = Some("Takeback declined".to_string());
+                    true
+                }
+                ServerMsg::ResetPosition { fen, .. } => {
+                    self.takeback = None;
+                    self.notice = None;
+                    match (&mut self.game, fen.parse()) {
+                        (Some(game), Ok(board)) => {
+                            game.board = board;
+                            game.last_move = None;
+                            true
+                        }
+                        (_, Err(err)) => {
+                            warn!("received invalid position {fen} ({err})");
+                            false
+                        }
+                        (None, _) => false,
+                    }
+                }
+                ServerMsg::Clocks {
+                    white,
+                    black,
+                    running,
+                } => {
+                    self.clocks = Some([white, black].map(Duration::from_millis));
+                    self.clock_started = running.then(Date::now);
+                    true
+                }
                 other => {
                     info!("received {other:?}");
                     false
                 }
             },
+            Msg::Send(msg) => {
+                self.send(msg);
+                false
+            }
+            Msg::ShowResult(show) => {
+                self.hide_result = !show;
+                true
+            }
+            Msg::SetNotifications(enabled) => {
+                ctx.link().send_future(async move {
+                    alerts::set_notifications(enabled).await;
+                    Msg::NotificationsChanged
+                });
+                false
+            }
+            Msg::NotificationsChanged => true,
+            Msg::Tick => self.clock_started.is_some() && self.game_over.is_none(),
         }
     }
+
+    fn rendered(&mut self, _ctx: &yew::Context<Self>, _first_render: bool) {
+        alerts::show_turn(self.is_my_turn());
+    }
+
+    fn destroy(&mut self, _ctx: &yew::Context<Self>) {
+        alerts::show_turn(false);
+    }
 }
@@ -42,6 +42,10 @@ impl Component for Join {
                         session: response.session,
                     }
                 }
+                403 => Msg::Error(
+                    "Rated games can only be joined with an account, and not against yourself"
+                        .to_string(),
+                ),
                 404 => Msg::Error("Unknown lobby".to_string()),
                 409 => Msg::Error("Game is already running".to_string()),
                 other => Msg::Error(format!(
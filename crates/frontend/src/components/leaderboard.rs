@@ -0,0 +1,127 @@
+use common::http::{LeaderboardEntry, RatingCategory};
+use reqwasm::http::Request;
+use yew::prelude::*;
+
+/// Highest rated players of a rating category
+#[derive(Debug)]
+pub struct Leaderboard {
+    category: RatingCategory,
+    entries: Option<Vec<LeaderboardEntry>>,
+    error: Option<String>,
+}
+
+pub enum Msg {
+    SetCategory(RatingCategory),
+    Loaded(RatingCategory, Vec<LeaderboardEntry>),
+    Error(String),
+}
+
+impl Leaderboard {
+    fn load(&self, ctx: &Context<Self>) {
+        let category = self.category;
+        ctx.link().send_future(async move {
+            let response = match Request::get(&format!("/api/leaderboard/{category}"))
+                .send()
+                .await
+            {
+                Ok(response) => response,
+                Err(err) => return Msg::Error(err.to_string()),
+            };
+            match response.json().await {
+                Ok(entries) => Msg::Loaded(category, entries),
+                Err(err) => Msg::Error(err.to_string()),
+            }
+        });
+    }
+}
+
+impl Component for Leaderboard {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(ctx: &Context<Self>) -> Self {
+        let leaderboard = Self {
+            category: RatingCategory::Blitz,
+            entries: None,
+            error: None,
+        };
+        leaderboard.load(ctx);
+        leaderboard
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let categories: Html = RatingCategory::ALL
+            .into_iter()
+            .map(|category| {
+                let onclick = ctx.link().callback(move |_| Msg::SetCategory(category));
+                html! {
+                    <button {onclick} disabled={category == self.category}>
+                        {category.name()}
+                    </button>
+                }
+            })
+            .collect();
+        let table = match (&self.entries, &self.error) {
+            (_, Some(err)) => html! { <p>{"An error occured: "} {err}</p> },
+            (None, None) => html! { <p>{"Loading..."}</p> },
+            (Some(entries), None) if entries.is_empty() => {
+                html! { <p>{"Nobody played a rated game yet"}</p> }
+            }
+            (Some(entries), None) => {
+                let rows: Html = entries
+                    .iter()
+                    .enumerate()
+                    .map(|(rank, entry)| {
+                        html! {
+                            <tr key={entry.username.clone()}>
+                                <td>{rank + 1}</td>
+                                <td title={entry.username.clone()}>{&entry.display_name}</td>
+                                <td>{format!("{} ±{}", entry.rating, entry.deviation)}</td>
+                                <td>{entry.games}</td>
+                            </tr>
+                        }
+                    })
+                    .collect();
+                html! {
+                    <table>
+                        <tr>
+                            <th>{"#"}</th>
+                            <th>{"Player"}</th>
+                            <th>{"Rating"}</th>
+                            <th>{"Games"}</th>
+                        </tr>
+                        {rows}
+                    </table>
+                }
+            }
+        };
+
+        html! {
+            <div>
+                <h3>{"Leaderboard"}</h3>
+                <div>{categories}</div>
+                {table}
+            </div>
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Msg::SetCategory(category) => {
+                self.category = category;
+                self.entries = None;
+                self.error = None;
+                self.load(ctx);
+            }
+            Msg::Loaded(category, entries) => {
+                // the category might have changed while loading
+                if category != self.category {
+                    return false;
+                }
+                self.entries = Some(entries);
+            }
+            Msg::Error(err) => self.error = Some(err),
+        }
+        true
+    }
+}
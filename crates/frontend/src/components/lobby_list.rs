@@ -0,0 +1,139 @@
+use common::{
+    http::LobbyInfo,
+    ws::{message::Message, ClientMsg, ServerMsg},
+};
+use futures::{
+    channel::oneshot,
+    future::{self, Either},
+    SinkExt, StreamExt,
+};
+use log::{info, warn};
+use reqwasm::websocket::futures::WebSocket;
+use yew::{platform::spawn_local, prelude::*};
+use yew_router::prelude::*;
+
+use crate::Route;
+
+/// Live list of public lobbies waiting for an opponent
+pub struct LobbyList {
+    lobbies: Vec<LobbyInfo>,
+    /// Closes the connection when dropped
+    _cancel: oneshot::Sender<()>,
+}
+
+pub enum Msg {
+    ReceivedMsg(ServerMsg),
+}
+
+impl Component for LobbyList {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(ctx: &Context<Self>) -> Self {
+        let (cancel, mut cancelled) = oneshot::channel::<()>();
+        let link = ctx.link().clone();
+        spawn_local(async move {
+            let hostname = web_sys::window().unwrap().location().hostname().unwrap();
+            let mut ws = WebSocket::open(&format!("ws://{hostname}:3000/ws")).unwrap();
+            let watch = ClientMsg::WatchLobbies.to_reqwasm_message().unwrap();
+            if let Err(err) = ws.send(watch).await {
+                info!("error watching lobbies: {err}");
+                return;
+            }
+            loop {
+                match future::select(ws.next(), &mut cancelled).await {
+                    Either::Left((Some(Ok(msg)), _)) => match Message::from_reqwasm_message(msg) {
+                        Ok(msg) => link.send_message(Msg::ReceivedMsg(msg)),
+                        Err(err) => info!("error deserializing message: {err}"),
+                    },
+                    Either::Left((Some(Err(err)), _)) => {
+                        info!("error receiving message: {err}");
+                        break;
+                    }
+                    Either::Left((None, _)) => break,
+                    Either::Right(_) => {
+                        _ = ws.close(None, None);
+                        break;
+                    }
+                }
+            }
+        });
+
+        Self {
+            lobbies: Vec::new(),
+            _cancel: cancel,
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        if self.lobbies.is_empty() {
+            return html! { <p>{"No open lobbies"}</p> };
+        }
+        let navigator = ctx.link().navigator().unwrap();
+        let rows: Html = self
+            .lobbies
+            .iter()
+            .map(|lobby| {
+                let join = {
+                    let navigator = navigator.clone();
+                    let id = lobby.id;
+                    move |_| navigator.push(&Route::Join { id })
+                };
+                let time_control = match lobby.time_control {
+                    Some(time_control) => time_control.to_string(),
+                    None => "Unlimited".to_string(),
+                };
+                html! {
+                    <tr key={lobby.id.to_string()}>
+                        <td>{time_control}</td>
+                        <td>{format!("{:?}", lobby.variant)}</td>
+                        <td>{if lobby.rated { "Rated" } else { "Casual" }}</td>
+                        <td><button onclick={join}>{"Join"}</button></td>
+                    </tr>
+                }
+            })
+            .collect();
+        html! {
+            <table>
+                <tr>
+                    <th>{"Time control"}</th>
+                    <th>{"Variant"}</th>
+                    <th>{"Mode"}</th>
+                    <th/>
+                </tr>
+                {rows}
+            </table>
+        }
+    }
+
+    fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Msg::ReceivedMsg(ServerMsg::LobbyOpened {
+                id,
+                time_control,
+                variant,
+                rated,
+            }) => {
+                // the initial snapshot may overlap with live updates
+                if self.lobbies.iter().any(|lobby| lobby.id == id) {
+                    return false;
+                }
+                self.lobbies.push(LobbyInfo {
+                    id,
+                    time_control,
+                    variant,
+                    rated,
+                });
+                true
+            }
+            Msg::ReceivedMsg(ServerMsg::LobbyClosed { id }) => {
+                self.lobbies.retain(|lobby| lobby.id != id);
+                true
+            }
+            Msg::ReceivedMsg(msg) => {
+                warn!("received {msg:?}, should only receive lobby updates");
+                false
+            }
+        }
+    }
+}
@@ -1,28 +1,58 @@
 use std::str::FromStr;
 
+use common::http::{TimeControl, UserInfo};
+use log::warn;
+use reqwasm::http::Request;
 use uuid::Uuid;
-use web_sys::HtmlInputElement;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
 use yew::prelude::*;
-use yew_router::scope_ext::RouterScopeExt;
+use yew_router::prelude::*;
 
+use super::lobby_list::LobbyList;
 use crate::Route;
 
+/// Time controls offered for matchmaking and public lobbies
+const TIME_CONTROLS: [(u32, u32); 6] = [(1, 0), (3, 0), (3, 2), (5, 0), (10, 0), (15, 10)];
+
+/// Whether the checkbox is rendered and checked
+fn is_checked(checkbox: &NodeRef) -> bool {
+    checkbox
+        .cast::<HtmlInputElement>()
+        .is_some_and(|checkbox| checkbox.checked())
+}
+
 #[derive(Debug, Default)]
 pub struct Menu {
     input_ref: NodeRef,
+    time_control_ref: NodeRef,
+    rated_ref: NodeRef,
     output: String,
+    /// The logged in user, `None` when playing as a guest
+    user: Option<UserInfo>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Msg {
     Error(String),
+    User(Option<UserInfo>),
+    Logout,
 }
 
 impl Component for Menu {
     type Message = Msg;
     type Properties = ();
 
-    fn create(_ctx: &Context<Self>) -> Self {
+    fn create(ctx: &Context<Self>) -> Self {
+        ctx.link().send_future(async {
+            match Request::get("/api/account/me").send().await {
+                Ok(response) if response.ok() => Msg::User(response.json().await.ok()),
+                Ok(_) => Msg::User(None),
+                Err(err) => {
+                    warn!("failed to fetch account: {err}");
+                    Msg::User(None)
+                }
+            }
+        });
         Self::default()
     }
 
@@ -33,6 +63,58 @@ impl Component for Menu {
                 navigator.push(&Route::Host);
             }
         };
+        let host_bot = {
+            let navigator = ctx.link().navigator().unwrap();
+            move |_| {
+                navigator.push(&Route::HostBot);
+            }
+        };
+        let offline = {
+            let navigator = ctx.link().navigator().unwrap();
+            move |_| {
+                navigator.push(&Route::Offline);
+            }
+        };
+        let seek = {
+            let time_control_ref = self.time_control_ref.clone();
+            let rated_ref = self.rated_ref.clone();
+            let navigator = ctx.link().navigator().unwrap();
+            move |_| {
+                let select: HtmlSelectElement = time_control_ref.cast().unwrap();
+                let rated = is_checked(&rated_ref);
+                if let Ok(time_control) = select.value().parse() {
+                    navigator.push(&Route::Seek {
+                        time_control,
+                        rated,
+                    });
+                }
+            }
+        };
+        let host_public = {
+            let time_control_ref = self.time_control_ref.clone();
+            let rated_ref = self.rated_ref.clone();
+            let navigator = ctx.link().navigator().unwrap();
+            move |_| {
+                let select: HtmlSelectElement = time_control_ref.cast().unwrap();
+                let rated = is_checked(&rated_ref);
+                if let Ok(time_control) = select.value().parse() {
+                    navigator.push(&Route::HostPublic {
+                        time_control,
+                        rated,
+                    });
+                }
+            }
+        };
+        let time_controls: Html = TIME_CONTROLS
+            .into_iter()
+            .map(|(minutes, increment)| {
+                let time_control = TimeControl {
+                    initial: minutes * 60,
+                    increment,
+                };
+                html! { <option value={time_control.to_string()}>{time_control.to_string()}</option> }
+            })
+            .collect();
         let join = {
             let input_ref = self.input_ref.clone();
             let link = ctx.link().clone();
@@ -51,23 +133,73 @@ impl Component for Menu {
             }
         };
 
+        let account = match &self.user {
+            Some(user) => html! {
+                <p>
+                    {format!("Playing as {} ", user.display_name)}
+                    <button onclick={ctx.link().callback(|_| Msg::Logout)}>{"Log out"}</button>
+                </p>
+            },
+            None => html! {
+                <p>
+                    {"Playing as a guest, "}
+                    <Link<Route> to={Route::Login}>{"log in"}</Link<Route>>
+                    {" or "}
+                    <Link<Route> to={Route::Register}>{"register"}</Link<Route>>
+                </p>
+            },
+        };
+
         html! {
             <div>
+                {account}
                 <button onclick={host}>{"Host Game"}</button>
+                <button onclick={host_bot}>{"Play vs Computer"}</button>
+                <button onclick={offline}>{"Play Offline"}</button>
+                <div>
+                    <select ref={&self.time_control_ref}>{time_controls}</select>
+                    if self.user.is_some() {
+                        <label>
+                            <input type={"checkbox"} ref={&self.rated_ref}/>
+                            {"Rated"}
+                        </label>
+                    }
+                    <button onclick={seek}>{"Find Opponent"}</button>
+                    <button onclick={host_public}>{"Host Public Game"}</button>
+                </div>
                 <div>
                     <input placeholder={"Lobby ID"} ref={&self.input_ref}/>
                     <button onclick={join}>{"Join Game"}</button>
                 </div>
                 <p>{&self.output}</p>
+                <h3>{"Open lobbies"}</h3>
+                <LobbyList />
+                <Link<Route> to={Route::Leaderboard}>{"Leaderboard"}</Link<Route>>
+                {" "}
+                <Link<Route> to={Route::Analysis}>{"Analysis board"}</Link<Route>>
+                {" "}
+                <Link<Route> to={Route::Editor}>{"Board editor"}</Link<Route>>
             </div>
         }
     }
 
-    fn update(&mut self, _ctx: &Context<Self>, msg: Msg) -> bool {
+    fn update(&mut self, ctx: &Context<Self>, msg: Msg) -> bool {
         match msg {
             Msg::Error(err) => {
                 self.output = err;
             }
+            Msg::User(user) => {
+                self.user = user;
+            }
+            Msg::Logout => {
+                ctx.link().send_future(async {
+                    match Request::post("/api/account/logout").send().await {
+                        Ok(_) => Msg::User(None),
+                        Err(err) => Msg::Error(format!("Failed to log out: {err}")),
+                    }
+                });
+                return false;
+            }
         }
         true
     }
@@ -0,0 +1,151 @@
+use std::time::Duration;
+
+use cozy_chess::{Board as ChessBoard, Color, GameStatus, Move};
+use engine::{Engine, Limits};
+use log::{info, warn};
+use web_sys::HtmlSelectElement;
+use yew::{platform::time::sleep, prelude::*};
+
+use super::board::Board;
+
+/// Game against the built-in engine, running entirely in the browser
+pub struct Offline {
+    board: ChessBoard,
+    color: Color,
+    last_move: Option<Move>,
+    level: u8,
+    engine: Engine,
+}
+
+pub enum Msg {
+    PlayMove(Move),
+    /// Let the engine search its reply, sent with a small delay so the board can render first
+    Think,
+    SetLevel(u8),
+    NewGame,
+}
+
+impl Offline {
+    fn new_game(&mut self, ctx: &Context<Self>) {
+        self.board = ChessBoard::default();
+        self.last_move = None;
+        self.color = if js_sys::Math::random() < 0.5 {
+            Color::White
+        } else {
+            Color::Black
+        };
+        self.engine.new_game();
+        self.think_if_needed(ctx);
+    }
+
+    fn think_if_needed(&self, ctx: &Context<Self>) {
+        if self.board.side_to_move() != self.color && self.board.status() == GameStatus::Ongoing {
+            ctx.link().send_future(async {
+                sleep(Duration::from_millis(100)).await;
+                Msg::Think
+            });
+        }
+    }
+}
+
+impl Component for Offline {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(ctx: &Context<Self>) -> Self {
+        let mut offline = Self {
+            board: ChessBoard::default(),
+            color: Color::White,
+            last_move: None,
+            level: Limits::DEFAULT_LEVEL,
+            engine: Engine::with_seed((js_sys::Math::random() * u64::MAX as f64) as u64),
+        };
+        offline.new_game(ctx);
+        offline
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let play_move = ctx.link().callback(Msg::PlayMove);
+        let set_level = ctx.link().callback(|e: Event| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            Msg::SetLevel(select.value().parse().unwrap_or(Limits::DEFAULT_LEVEL))
+        });
+        let new_game = ctx.link().callback(|_| Msg::NewGame);
+
+        let levels: Html = (Limits::MIN_LEVEL..=Limits::MAX_LEVEL)
+            .map(|level| {
+                html! {
+                    <option value={level.to_string()} selected={level == self.level}>
+                        {format!("Level {level}")}
+                    </option>
+                }
+            })
+            .collect();
+        let status = match self.board.status() {
+            GameStatus::Ongoing if self.board.side_to_move() == self.color => "Your turn",
+            GameStatus::Ongoing => "The computer is thinking...",
+            GameStatus::Drawn => "Draw",
+            GameStatus::Won if self.board.side_to_move() == self.color => "You lost",
+            GameStatus::Won => "You won",
+        };
+
+        html! {
+            <>
+                <div>
+                    <select onchange={set_level}>{levels}</select>
+                    <button onclick={new_game}>{"New Game"}</button>
+                </div>
+                <p>{status}</p>
+                <Board
+                    board={self.board.clone()}
+                    color={self.color}
+                    last_move={self.last_move}
+                    {play_move}
+                />
+            </>
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Msg::PlayMove(chess_move) => {
+                if self.board.side_to_move() != self.color {
+                    info!("tried to move while the computer is thinking, ignoring");
+                    return false;
+                }
+                if let Err(err) = self.board.try_play(chess_move) {
+                    warn!("tried to play invalid move {chess_move} ({err})");
+                    return false;
+                }
+                self.last_move = Some(chess_move);
+                self.think_if_needed(ctx);
+                true
+            }
+            Msg::Think => {
+                if self.board.side_to_move() == self.color {
+                    return false;
+                }
+                match self.engine.search(&self.board, Limits::level(self.level)) {
+                    Some(result) => {
+                        info!(
+                            "engine played {} (score {}, depth {}, {} nodes)",
+                            result.best_move, result.score, result.depth, result.nodes
+                        );
+                        self.board.play_unchecked(result.best_move);
+                        self.last_move = Some(result.best_move);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            Msg::SetLevel(level) => {
+                self.level = level;
+                false
+            }
+            Msg::NewGame => {
+                self.new_game(ctx);
+                true
+            }
+        }
+    }
+}
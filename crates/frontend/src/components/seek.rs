@@ -0,0 +1,153 @@
+use common::{
+    http::{SeekRequest, SeekResponse, TimeControl},
+    ws::{message::Message, ClientMsg, ServerMsg},
+};
+use futures::{
+    channel::oneshot,
+    future::{self, Either},
+    SinkExt, StreamExt,
+};
+use log::{info, warn};
+use reqwasm::{http::Request, websocket::futures::WebSocket};
+use yew::{platform::spawn_local, prelude::*};
+use yew_router::prelude::*;
+
+use crate::Route;
+
+/// Waits in the matchmaking queue until an opponent with the same time control is found
+pub struct Seek {
+    /// Closes the connection, and with it the seek, when dropped
+    _cancel: oneshot::Sender<()>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Properties)]
+pub struct Props {
+    pub time_control: TimeControl,
+    #[prop_or_default]
+    pub rated: bool,
+}
+
+pub enum Msg {
+    Error(String),
+    ReceivedMsg(ServerMsg),
+}
+
+async fn post_seek(time_control: TimeControl, rated: bool) -> Result<SeekResponse, String> {
+    let request = SeekRequest {
+        time_control,
+        rating_range: None,
+        rated,
+    };
+    let response = Request::post("/api/seek")
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_string(&request).unwrap())
+        .send()
+        .await
+        .map_err(|err| err.to_string())?;
+    match response.status() {
+        200 => response.json().await.map_err(|err| err.to_string()),
+        401 => Err("Log in to play rated games".to_string()),
+        other => Err(format!(
+            "Unhandled status code {other} ({})",
+            response.status_text(),
+        )),
+    }
+}
+
+impl Component for Seek {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        let (cancel, mut cancelled) = oneshot::channel::<()>();
+        let Props {
+            time_control,
+            rated,
+        } = *ctx.props();
+        let link = ctx.link().clone();
+        spawn_local(async move {
+            let seek = match post_seek(time_control, rated).await {
+                Ok(seek) => seek,
+                Err(err) => {
+                    link.send_message(Msg::Error(err));
+                    return;
+                }
+            };
+            let hostname = web_sys::window().unwrap().location().hostname().unwrap();
+            let mut ws = WebSocket::open(&format!("ws://{hostname}:3000/ws")).unwrap();
+            let wait = ClientMsg::WaitForMatch {
+                seek_id: seek.seek_id,
+            };
+            if let Err(err) = ws.send(wait.to_reqwasm_message().unwrap()).await {
+                link.send_message(Msg::Error(err.to_string()));
+                return;
+            }
+            loop {
+                match future::select(ws.next(), &mut cancelled).await {
+                    Either::Left((Some(Ok(msg)), _)) => match Message::from_reqwasm_message(msg) {
+                        Ok(msg) => link.send_message(Msg::ReceivedMsg(msg)),
+                        Err(err) => info!("error deserializing message: {err}"),
+                    },
+                    Either::Left((Some(Err(err)), _)) => {
+                        info!("error receiving message: {err}");
+                        break;
+                    }
+                    Either::Left((None, _)) => break,
+                    Either::Right(_) => {
+                        _ = ws.close(None, None);
+                        break;
+                    }
+                }
+            }
+        });
+
+        Self {
+            _cancel: cancel,
+            error: None,
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let cancel = {
+            let navigator = ctx.link().navigator().unwrap();
+            move |_| navigator.push(&Route::Home)
+        };
+        let status = match &self.error {
+            Some(err) => html! { <p>{"An error occured: "} {err}</p> },
+            None => html! {
+                <p>{format!("Searching for an opponent ({})...", ctx.props().time_control)}</p>
+            },
+        };
+        html! {
+            <>
+                {status}
+                <button onclick={cancel}>{"Cancel"}</button>
+            </>
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Msg::Error(err) => {
+                self.error.replace(err);
+                true
+            }
+            Msg::ReceivedMsg(ServerMsg::Matched { lobby_id, session }) => {
+                ctx.link().navigator().unwrap().push(&Route::Ingame {
+                    id: lobby_id,
+                    session,
+                });
+                false
+            }
+            Msg::ReceivedMsg(ServerMsg::InvalidSeek) => {
+                self.error.replace("The seek expired".to_string());
+                true
+            }
+            Msg::ReceivedMsg(msg) => {
+                warn!("received {msg:?}, should only receive Matched");
+                false
+            }
+        }
+    }
+}
@@ -1,11 +1,15 @@
 use crate::components::{
-    host::Host, ingame::Ingame, join::Join, menu::Menu, waiting_for_opponent::WaitingForOpponent,
+    account::Account, analysis::Analysis, editor::Editor, host::Host, ingame::Ingame, join::Join,
+    leaderboard::Leaderboard, menu::Menu, offline::Offline, seek::Seek,
+    waiting_for_opponent::WaitingForOpponent,
 };
-use common::http::{HostResponse, JoinResponse};
+use common::http::{HostResponse, JoinResponse, LobbySettings, TimeControl};
 use uuid::Uuid;
 use yew::prelude::*;
 use yew_router::prelude::*;
 
+mod alerts;
+mod appearance;
 mod components;
 
 #[derive(Routable, PartialEq, Clone, Copy, Debug)]
@@ -14,6 +18,13 @@ enum Route {
     Home,
     #[at("/host")]
     Host,
+    #[at("/host/bot")]
+    HostBot,
+    #[at("/host/public/:time_control/:rated")]
+    HostPublic {
+        time_control: TimeControl,
+        rated: bool,
+    },
     // TODO: Do not include session in url
     #[at("/waiting-for-opponent/:id/:session")]
     WaitingForOpponent { id: Uuid, session: Uuid },
@@ -22,6 +33,25 @@ enum Route {
     // TODO: Do not include session in url
     #[at("/game/:id/:session")]
     Ingame { id: Uuid, session: Uuid },
+    #[at("/seek/:time_control/:rated")]
+    Seek {
+        time_control: TimeControl,
+        rated: bool,
+    },
+    #[at("/offline")]
+    Offline,
+    #[at("/login")]
+    Login,
+    #[at("/register")]
+    Register,
+    #[at("/leaderboard")]
+    Leaderboard,
+    #[at("/analysis")]
+    Analysis,
+    #[at("/analysis/:id")]
+    AnalyzeGame { id: Uuid },
+    #[at("/editor")]
+    Editor,
     #[at("/not-found")]
     #[not_found]
     NotFound,
@@ -55,11 +85,36 @@ fn switch(route: Route) -> Html {
     match route {
         Route::Home => html! { <Menu /> },
         Route::Host => html! { <Host /> },
+        Route::HostBot => html! { <Host bot=true /> },
+        Route::HostPublic {
+            time_control,
+            rated,
+        } => {
+            let settings = LobbySettings {
+                public: true,
+                time_control: Some(time_control),
+                rated,
+                ..LobbySettings::default()
+            };
+            html! { <Host {settings} /> }
+        }
         Route::WaitingForOpponent { id, session } => {
             html! { <WaitingForOpponent {id} {session} /> }
         }
         Route::Join { id } => html! { <Join {id} /> },
-        Route::Ingame { id, session } => html! { <Ingame {id} {session} /> },
+        // rematches are played in a new lobby, the key makes sure to connect again
+        Route::Ingame { id, session } => html! { <Ingame key={id.to_string()} {id} {session} /> },
+        Route::Seek {
+            time_control,
+            rated,
+        } => html! { <Seek {time_control} {rated} /> },
+        Route::Offline => html! { <Offline /> },
+        Route::Login => html! { <Account /> },
+        Route::Register => html! { <Account register=true /> },
+        Route::Leaderboard => html! { <Leaderboard /> },
+        Route::Analysis => html! { <Analysis /> },
+        Route::AnalyzeGame { id } => html! { <Analysis key={id.to_string()} game={id} /> },
+        Route::Editor => html! { <Editor /> },
         Route::NotFound => html! { "Not Found." },
     }
 }
@@ -58,14 +58,14 @@ pub fn derive_message(input: StdTokenStream) -> StdTokenStream {
                             let ty = &field.ty;
                             quote_spanned! {
                                 field.span() =>
-                                <#ty as message::Message>::encode(&#ident, &mut write)?;
+                                <#ty as message::Message>::encode_with(#ident, ctx)?;
                             }
                         })
                         .collect();
                     let ident = variant.ident.clone();
                     quote! {
                         Self::#ident #fields => {
-                            #idx.encode(&mut write)?;
+                            <u8 as message::Message>::encode_with(&#idx, ctx)?;
                             #field_encodes
                         },
                     }
@@ -119,7 +119,7 @@ pub fn derive_message(input: StdTokenStream) -> StdTokenStream {
                             let ty = &field.ty;
                             quote_spanned! {
                                 field.span() =>
-                                let #ident = <#ty as message::Message>::decode(&mut read)?;
+                                let #ident = <#ty as message::Message>::decode_with(ctx)?;
                             }
                         })
                         .collect();
@@ -135,19 +135,19 @@ pub fn derive_message(input: StdTokenStream) -> StdTokenStream {
             let invalid_str = format!("invalid {ident} id {{}}");
             quote! {
                 impl message::Message for #ident {
-                    fn encode(&self, mut write: impl std::io::Write) -> std::io::Result<()> {
+                    fn encode_with<W: std::io::Write>(&self, ctx: &mut message::EncodeContext<W>) -> std::io::Result<()> {
                         match self {
                             #encodes
                         }
-                        write.flush()
+                        Ok(())
                     }
 
-                    fn decode(mut read: impl std::io::Read) -> std::io::Result<Self> {
-                        let id = u8::decode(&mut read)?;
-                        match id {
+                    fn decode_with<R: std::io::Read>(ctx: &mut message::DecodeContext<R>) -> std::io::Result<Self> {
+                        let id = <u8 as message::Message>::decode_with(ctx)?;
+                        ctx.nested(|ctx| match id {
                             #decodes
                             _ => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!(#invalid_str, id))),
-                        }
+                        })
                     }
                 }
             }
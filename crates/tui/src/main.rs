@@ -0,0 +1,596 @@
+use client::{Client, Connection};
+use common::{
+    http::{SeekRequest, TimeControl},
+    notation,
+    ws::{ClientMsg, GameResult, GameState, RatingChange, ServerMsg},
+};
+use cozy_chess::{Board, Color, File, GameStatus, Piece, Rank, Square};
+use crossterm::{
+    event::{Event, EventStream, KeyCode, KeyEvent, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use futures::StreamExt;
+use ratatui::{
+    backend::{Backend, CrosstermBackend},
+    layout::{Constraint, Direction, Layout},
+    style::{Color as TermColor, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Paragraph, Wrap},
+    Frame, Terminal,
+};
+use std::{
+    error::Error,
+    io,
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+const USAGE: &str = "usage: tui [--server <url>] (host | join <lobby id> | seek <time control>)";
+
+/// Reconnection attempts after losing the connection before giving up
+const MAX_RECONNECTS: u32 = 5;
+/// Wait before the first reconnection attempt, doubled after every failed attempt
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+
+enum Command {
+    Host,
+    Join(Uuid),
+    Seek(TimeControl),
+}
+
+struct Args {
+    server: String,
+    command: Command,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut server = "http://localhost:3000".to_string();
+    let mut command = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--server" => server = args.next().ok_or(USAGE)?,
+            "host" => command = Some(Command::Host),
+            "join" => {
+                let id = args.next().ok_or(USAGE)?;
+                let id = id
+                    .parse()
+                    .map_err(|err| format!("Invalid lobby id '{id}': {err}"))?;
+                command = Some(Command::Join(id));
+            }
+            "seek" => {
+                let time_control = args.next().ok_or(USAGE)?;
+                let time_control = time_control.parse().map_err(|err| format!("{err}"))?;
+                command = Some(Command::Seek(time_control));
+            }
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    Ok(Args {
+        server,
+        command: command.ok_or(USAGE)?,
+    })
+}
+
+struct Game {
+    board: Board,
+    color: Color,
+    history: Vec<String>,
+    /// Display names of both players, once received
+    players: Option<[String; Color::NUM]>,
+    /// Time used by each side, measured locally in games without a time control
+    clocks: [Duration; Color::NUM],
+    turn_start: Instant,
+    /// Remaining time of each side as sent by the server in games with a time control, the
+    /// side to move's time counts down from `turn_start` if the clock is running
+    remaining: Option<[Duration; Color::NUM]>,
+    running: bool,
+    over: bool,
+}
+
+impl Game {
+    fn new(board: Board, color: Color) -> Self {
+        Self {
+            board,
+            color,
+            history: Vec::new(),
+            players: None,
+            clocks: [Duration::ZERO; Color::NUM],
+            turn_start: Instant::now(),
+            remaining: None,
+            running: false,
+            over: false,
+        }
+    }
+
+    fn clock(&self, color: Color) -> Duration {
+        let moving = !self.over
+            && self.board.side_to_move() == color
+            && self.board.status() == GameStatus::Ongoing;
+        match self.remaining {
+            Some(remaining) if moving && self.running => {
+                remaining[color as usize].saturating_sub(self.turn_start.elapsed())
+            }
+            Some(remaining) => remaining[color as usize],
+            None if moving => self.clocks[color as usize] + self.turn_start.elapsed(),
+            None => self.clocks[color as usize],
+        }
+    }
+}
+
+struct App {
+    lobby_id: Uuid,
+    game: Option<Game>,
+    input: String,
+    status: String,
+}
+
+enum Action {
+    None,
+    Quit,
+    Reconnect,
+    /// The connection was closed unexpectedly
+    ConnectionLost,
+    /// The server rejected the connection, reconnecting won't help
+    Fail(String),
+}
+
+fn is_quit(key: KeyEvent) -> bool {
+    key.code == KeyCode::Esc
+        || (key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL))
+}
+
+impl App {
+    fn handle_msg(&mut self, msg: ServerMsg) -> Action {
+        match msg {
+            ServerMsg::Connected(GameState::WaitingForOpponent) => {
+                self.status = format!("Waiting for your opponent, lobby id: {}", self.lobby_id);
+            }
+            ServerMsg::Connected(GameState::Ingame { fen, color }) => match fen.parse() {
+                Ok(board) => {
+                    self.game = Some(Game::new(board, color));
+                    self.status = "Game started".to_string();
+                }
+                Err(err) => self.status = format!("Received invalid position: {err}"),
+            },
+            ServerMsg::OpponentJoined => return Action::Reconnect,
+            ServerMsg::PlayedMove(chess_move) => {
+                if let Some(game) = &mut self.game {
+                    if game.board.is_legal(chess_move) {
+                        let side = game.board.side_to_move();
+                        game.clocks[side as usize] += game.turn_start.elapsed();
+                        game.turn_start = Instant::now();
+                        game.history.push(notation::to_san(&game.board, chess_move));
+                        game.board.play_unchecked(chess_move);
+                        self.status = String::new();
+                    } else {
+                        self.status = format!("Received illegal move {chess_move}");
+                    }
+                }
+            }
+            ServerMsg::Players { white, black } => {
+                if let Some(game) = &mut self.game {
+                    game.players = Some([white, black]);
+                }
+            }
+            ServerMsg::GameOver {
+                result,
+                reason,
+                ratings,
+            } => {
+                if let Some(game) = &mut self.game {
+                    game.over = true;
+                    let title = match (result, game.color) {
+                        (GameResult::Draw, _) => "Draw",
+                        (GameResult::WhiteWins, Color::White)
+                        | (GameResult::BlackWins, Color::Black) => "You won",
+                        _ => "You lost",
+                    };
+                    self.status = format!("{title} by {reason}");
+                    if let Some(ratings) = ratings {
+                        let RatingChange { rating, delta } = ratings[game.color as usize];
+                        self.status += &format!(", your rating is now {rating} ({delta:+})");
+                    }
+                }
+            }
+            ServerMsg::TakebackRequested(color) => {
+                if let Some(game) = &self.game {
+                    self.status = if color == game.color {
+                        "Asked your opponent to take back".to_string()
+                    } else {
+                        "Your opponent wants to take back, type accept or decline".to_string()
+                    };
+                }
+            }
+            ServerMsg::TakebackDeclined => self.status = "Takeback declined".to_string(),
+            ServerMsg::ResetPosition { fen, plies } => match (&mut self.game, fen.parse()) {
+                (Some(game), Ok(board)) => {
+                    game.board = board;
+                    game.history
+                        .truncate(game.history.len().saturating_sub(plies));
+                    game.turn_start = Instant::now();
+                    self.status = "Took back".to_string();
+                }
+                (_, Err(err)) => self.status = format!("Received invalid position: {err}"),
+                (None, _) => {}
+            },
+            ServerMsg::RematchOffered(color) => {
+                if let Some(game) = &self.game {
+                    self.status = if color == game.color {
+                        "Offered a rematch".to_string()
+                    } else {
+                        "Your opponent wants a rematch, type rematch to accept".to_string()
+                    };
+                }
+            }
+            ServerMsg::Rematch { lobby_id } => {
+                self.lobby_id = lobby_id;
+                return Action::Reconnect;
+            }
+            ServerMsg::Clocks {
+                white,
+                black,
+                running,
+            } => {
+                if let Some(game) = &mut self.game {
+                    game.remaining = Some([white, black].map(Duration::from_millis));
+                    game.running = running;
+                    game.turn_start = Instant::now();
+                }
+            }
+            ServerMsg::InvalidMove => self.status = "Invalid move".to_string(),
+            // the server closes the connection after these
+            ServerMsg::InvalidSession => return Action::Fail("Invalid session".to_string()),
+            ServerMsg::InvalidLobby => return Action::Fail("Unknown lobby".to_string()),
+            ServerMsg::ConnectRequired => self.status = "Not connected".to_string(),
+            msg @ (ServerMsg::Matched { .. }
+            | ServerMsg::InvalidSeek
+            | ServerMsg::LobbyOpened { .. }
+            | ServerMsg::LobbyClosed { .. }) => {
+                self.status = format!("Unexpected message {msg:?}");
+            }
+        }
+        Action::None
+    }
+
+    async fn handle_key(&mut self, key: KeyEvent, connection: &mut Connection) -> Action {
+        if is_quit(key) {
+            return Action::Quit;
+        }
+        match key.code {
+            KeyCode::Char(c) => self.input.push(c),
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Enter => {
+                let input = std::mem::take(&mut self.input);
+                let Some(game) = &self.game else {
+                    self.status = "The game hasn't started yet".to_string();
+                    return Action::None;
+                };
+                let command = match input.trim() {
+                    "takeback" => Some(ClientMsg::RequestTakeback),
+                    "accept" => Some(ClientMsg::AcceptTakeback),
+                    "decline" => Some(ClientMsg::DeclineTakeback),
+                    "rematch" => Some(ClientMsg::OfferRematch),
+                    _ => None,
+                };
+                if let Some(msg) = command {
+                    if let Err(err) = connection.send(msg).await {
+                        self.status = format!("Failed to send {input}: {err}");
+                    }
+                    return Action::None;
+                }
+                if game.board.side_to_move() != game.color {
+                    self.status = "It's not your turn".to_string();
+                    return Action::None;
+                }
+                match notation::parse_move(&game.board, &input) {
+                    Ok(chess_move) => {
+                        if let Err(err) = connection.play_move(chess_move).await {
+                            self.status = format!("Failed to send move: {err}");
+                        }
+                    }
+                    Err(err) => self.status = err.to_string(),
+                }
+            }
+            _ => {}
+        }
+        Action::None
+    }
+}
+
+fn piece_symbol(piece: Piece) -> &'static str {
+    match piece {
+        Piece::Pawn => "♟",
+        Piece::Knight => "♞",
+        Piece::Bishop => "♝",
+        Piece::Rook => "♜",
+        Piece::Queen => "♛",
+        Piece::King => "♚",
+    }
+}
+
+fn board_lines(game: &Game) -> Vec<Spans<'static>> {
+    let mut ranks = Rank::ALL.to_vec();
+    let mut files = File::ALL.to_vec();
+    if game.color == Color::White {
+        ranks.reverse();
+    } else {
+        files.reverse();
+    }
+    let mut lines: Vec<Spans> = ranks
+        .iter()
+        .map(|&rank| {
+            let mut spans = vec![Span::raw(format!("{rank} "))];
+            spans.extend(files.iter().map(|&file| {
+                let square = Square::new(file, rank);
+                let background = if (file as usize + rank as usize).is_multiple_of(2) {
+                    TermColor::Rgb(181, 136, 99)
+                } else {
+                    TermColor::Rgb(240, 217, 181)
+                };
+                let (symbol, foreground) =
+                    match game.board.piece_on(square).zip(game.board.color_on(square)) {
+                        Some((piece, Color::White)) => (piece_symbol(piece), TermColor::White),
+                        Some((piece, Color::Black)) => (piece_symbol(piece), TermColor::Black),
+                        None => (" ", TermColor::Reset),
+                    };
+                Span::styled(
+                    format!(" {symbol} "),
+                    Style::default()
+                        .fg(foreground)
+                        .bg(background)
+                        .add_modifier(Modifier::BOLD),
+                )
+            }));
+            Spans::from(spans)
+        })
+        .collect();
+    let labels: String = files.iter().map(|file| format!(" {file} ")).collect();
+    lines.push(Spans::from(format!("  {labels}")));
+    lines
+}
+
+fn format_clock(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{:02}:{:02}", secs / 60, secs % 60)
+}
+
+fn draw<B: Backend>(frame: &mut Frame<B>, app: &App) {
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(30), Constraint::Min(20)])
+        .split(frame.size());
+    let panel = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(4),
+            Constraint::Min(3),
+            Constraint::Length(3),
+            Constraint::Length(3),
+        ])
+        .split(columns[1]);
+
+    let board = match &app.game {
+        Some(game) => Paragraph::new(board_lines(game)),
+        None => Paragraph::new("Waiting for the game to start...").wrap(Wrap { trim: true }),
+    };
+    frame.render_widget(
+        board.block(Block::default().borders(Borders::ALL)),
+        columns[0],
+    );
+
+    if let Some(game) = &app.game {
+        let clocks: Vec<Spans> = [Color::White, Color::Black]
+            .into_iter()
+            .map(|color| {
+                let name = if color == Color::White {
+                    "White"
+                } else {
+                    "Black"
+                };
+                let player = match &game.players {
+                    Some(players) => format!(" {}", players[color as usize]),
+                    None => String::new(),
+                };
+                let you = if color == game.color { " (you)" } else { "" };
+                let style = if game.board.side_to_move() == color {
+                    Style::default().add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                Spans::from(Span::styled(
+                    format!("{name}{player}{you}: {}", format_clock(game.clock(color))),
+                    style,
+                ))
+            })
+            .collect();
+        frame.render_widget(
+            Paragraph::new(clocks).block(Block::default().title("Clocks").borders(Borders::ALL)),
+            panel[0],
+        );
+
+        let history: Vec<Spans> = game
+            .history
+            .chunks(2)
+            .enumerate()
+            .map(|(idx, moves)| Spans::from(format!("{}. {}", idx + 1, moves.join(" "))))
+            .collect();
+        let visible = panel[1].height.saturating_sub(2) as usize;
+        let scroll = history.len().saturating_sub(visible) as u16;
+        frame.render_widget(
+            Paragraph::new(history)
+                .scroll((scroll, 0))
+                .block(Block::default().title("Moves").borders(Borders::ALL)),
+            panel[1],
+        );
+    }
+
+    frame.render_widget(
+        Paragraph::new(app.status.as_str()).block(Block::default().borders(Borders::ALL)),
+        panel[2],
+    );
+    frame.render_widget(
+        Paragraph::new(format!("> {}", app.input)).block(
+            Block::default()
+                .title("Move (UCI or SAN), takeback, accept, decline or rematch (Esc to quit)")
+                .borders(Borders::ALL),
+        ),
+        panel[3],
+    );
+    frame.set_cursor(panel[3].x + 3 + app.input.len() as u16, panel[3].y + 1);
+}
+
+/// Connects again after the connection was lost, waiting twice as long after every failure.
+///
+/// `failures` counts the attempts since the last received message, returns `None` if the
+/// user quits while waiting.
+async fn reconnect<B: Backend>(
+    terminal: &mut Terminal<B>,
+    events: &mut EventStream,
+    app: &mut App,
+    client: &Client,
+    session: Uuid,
+    failures: &mut u32,
+) -> Result<Option<Connection>, Box<dyn Error>> {
+    loop {
+        if *failures >= MAX_RECONNECTS {
+            return Err(format!("Lost the connection after {MAX_RECONNECTS} attempts").into());
+        }
+        let delay = RECONNECT_DELAY * 2u32.pow(*failures);
+        *failures += 1;
+        app.status = format!(
+            "Connection lost, reconnecting in {:.1}s ({failures}/{MAX_RECONNECTS})",
+            delay.as_secs_f32()
+        );
+        terminal.draw(|frame| draw(frame, app))?;
+
+        let deadline = tokio::time::Instant::now() + delay;
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep_until(deadline) => break,
+                event = events.next() => match event {
+                    Some(Ok(Event::Key(key))) if is_quit(key) => return Ok(None),
+                    Some(Ok(_)) => {}
+                    Some(Err(err)) => return Err(err.into()),
+                    None => return Ok(None),
+                },
+            }
+        }
+
+        match client.connect(app.lobby_id, session).await {
+            Ok(connection) => return Ok(Some(connection)),
+            Err(err) => app.status = format!("Failed to reconnect: {err}"),
+        }
+    }
+}
+
+async fn run<B: Backend>(
+    terminal: &mut Terminal<B>,
+    client: &Client,
+    lobby_id: Uuid,
+    session: Uuid,
+) -> Result<(), Box<dyn Error>> {
+    let mut connection = client.connect(lobby_id, session).await?;
+    let mut events = EventStream::new();
+    let mut ticker = tokio::time::interval(Duration::from_millis(250));
+    let mut app = App {
+        lobby_id,
+        game: None,
+        input: String::new(),
+        status: "Connecting...".to_string(),
+    };
+
+    // failed reconnection attempts since the last received message
+    let mut failures = 0;
+
+    loop {
+        terminal.draw(|frame| draw(frame, &app))?;
+        let action = tokio::select! {
+            msg = connection.next() => match msg {
+                Some(msg) => {
+                    failures = 0;
+                    app.handle_msg(msg)
+                }
+                None => Action::ConnectionLost,
+            },
+            event = events.next() => match event {
+                Some(Ok(Event::Key(key))) => app.handle_key(key, &mut connection).await,
+                Some(Ok(_)) => Action::None,
+                Some(Err(err)) => return Err(err.into()),
+                None => Action::Quit,
+            },
+            _ = ticker.tick() => Action::None,
+        };
+        match action {
+            Action::None => {}
+            Action::Quit => return Ok(()),
+            Action::Reconnect => connection = client.connect(app.lobby_id, session).await?,
+            Action::ConnectionLost => {
+                match reconnect(
+                    terminal,
+                    &mut events,
+                    &mut app,
+                    client,
+                    session,
+                    &mut failures,
+                )
+                .await?
+                {
+                    Some(reconnected) => connection = reconnected,
+                    None => return Ok(()),
+                }
+            }
+            Action::Fail(err) => return Err(err.into()),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(2);
+        }
+    };
+
+    let client = Client::new(&args.server);
+    let (lobby_id, session) = match args.command {
+        Command::Host => {
+            let response = client.host().await?;
+            (response.lobby_id, response.session)
+        }
+        Command::Join(id) => {
+            let response = client.join(id).await?;
+            (response.lobby_id, response.session)
+        }
+        Command::Seek(time_control) => {
+            let seek = client
+                .seek(SeekRequest {
+                    time_control,
+                    rating_range: None,
+                    rated: false,
+                })
+                .await?;
+            println!("Searching for an opponent ({time_control})...");
+            let response = client.wait_for_match(seek.seek_id).await?;
+            (response.lobby_id, response.session)
+        }
+    };
+
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+
+    let res = run(&mut terminal, &client, lobby_id, session).await;
+
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+
+    res
+}
@@ -0,0 +1,12 @@
+#![no_main]
+
+use common::ws::{message::Message, ClientMsg};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(msg) = ClientMsg::decode(data) {
+        let mut buf = Vec::new();
+        msg.encode(&mut buf).unwrap();
+        assert_eq!(buf, data);
+    }
+});